@@ -0,0 +1,500 @@
+use chrono::prelude::*;
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::checks::MANAGER_CHECK;
+use crate::data::{
+    cases, config, event, get_connection,
+    moderation::{self, Action, ActionKind},
+};
+use crate::scheduler::{self, Job};
+use crate::util::{format_duration, format_time, parse_duration, parse_user_id};
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Warn a user, optionally for a case. The user is sent the reason."]
+#[usage = "<user> [#<case>] <reason>"]
+#[example = "@someone, #12, please keep the language civil"]
+#[only_in("guilds")]
+#[min_args(2)]
+pub fn warn(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    take_action(ctx, msg, args, ActionKind::Warn, user, None)
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Prevent a user from talking for some time, optionally for a case. The mute is lifted automatically."]
+#[usage = "<user> <duration> [#<case>] <reason>"]
+#[example = "@someone, 30m, #12, spamming in #general"]
+#[only_in("guilds")]
+#[min_args(3)]
+pub fn mute(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if config::load(&mut client)?.muted_role.is_none() {
+        msg.channel_id.say(
+            &ctx.http,
+            "Muting is not configured, set `muted_role` with `~admin config set` first",
+        )?;
+        return Ok(());
+    }
+
+    // Get the duration from args
+    let duration = match args.single::<String>() {
+        Ok(raw) => match parse_duration(&raw) {
+            Some(duration) => duration,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <duration>: '{}' must be like `30s`, `10m`, `2h`, or `1d`",
+                        raw
+                    ),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <duration> not satisfied")?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    take_action(ctx, msg, args, ActionKind::Mute, user, Some(duration))
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Lift a user's mute before it expires"]
+#[usage = "<user> [<reason>]"]
+#[example = "@someone, apologized"]
+#[only_in("guilds")]
+#[min_args(1)]
+pub fn unmute(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if moderation::mute(&mut client, user.0)?.is_none() {
+        msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .mention(&user)
+                .push(" is not muted")
+                .build(),
+        )?;
+        return Ok(());
+    }
+
+    let reason = match args.rest() {
+        "" => "Lifted by a moderator",
+        reason => reason,
+    };
+    lift_mute(ctx, &mut client, user, msg.author.id, reason)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Unmuted ")
+            .mention(&user)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Kick a user from the server, optionally for a case. The user is sent the reason."]
+#[usage = "<user> [#<case>] <reason>"]
+#[example = "@someone, #12, repeated harassment"]
+#[only_in("guilds")]
+#[min_args(2)]
+pub fn kick(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    take_action(ctx, msg, args, ActionKind::Kick, user, None)
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Ban a user from the server, optionally for a case. The user is sent the reason."]
+#[usage = "<user> [#<case>] <reason>"]
+#[example = "@someone, #12, posting NSFW content"]
+#[only_in("guilds")]
+#[min_args(2)]
+pub fn ban(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    take_action(ctx, msg, args, ActionKind::Ban, user, None)
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "List all the moderation actions taken against a user"]
+#[usage = "<user>"]
+#[example = "@someone"]
+#[num_args(1)]
+pub fn history(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let user = match parse_user(ctx, msg, &mut args)? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let actions = moderation::history(&mut client, user.0)?;
+    let timezone = event::timezone(&mut client)?;
+    if actions.is_empty() {
+        msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push("No actions have been taken against ")
+                .mention(&user)
+                .build(),
+        )?;
+        return Ok(());
+    }
+
+    // Only the most recent actions fit in an embed
+    let lines = actions
+        .iter()
+        .rev()
+        .take(20)
+        .map(|a| {
+            let mut line = format!(
+                "`#{}` **{}** by <@{}> on {}",
+                a.id,
+                a.kind,
+                a.moderator,
+                format_time(a.created_at, timezone)
+            );
+            if let Some(duration) = a.duration {
+                line.push_str(&format!(" for {}", format_duration(duration)));
+            }
+            if let Some(case) = a.case {
+                line.push_str(&format!(" (case `#{}`)", case));
+            }
+            line.push_str(&format!(": {}", a.reason));
+            line
+        })
+        .collect::<Vec<_>>();
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Moderation history")
+                .description(
+                    MessageBuilder::new()
+                        .mention(&user)
+                        .push("\n\n")
+                        .push(lines.join("\n"))
+                        .build(),
+                )
+                .footer(|f| f.text(format!("{} actions in total", actions.len())))
+        })
+    })?;
+
+    Ok(())
+}
+
+// Lift a mute that is due, unless it was replaced by a later one
+pub fn expire_mute(ctx: &Context, user: u64, action: u64) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    if moderation::mute(&mut client, user)? != Some(action) {
+        return Ok(());
+    }
+
+    let bot_id = ctx.cache.read().user.id;
+    lift_mute(ctx, &mut client, UserId(user), bot_id, "Mute expired")
+}
+
+// Parse the case and reason for an action from the args and perform it
+fn take_action(
+    ctx: &Context,
+    msg: &Message,
+    mut args: Args,
+    kind: ActionKind,
+    user: UserId,
+    duration: Option<i64>,
+) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+
+    // Get the optional originating case, which must be prefixed with '#'
+    let mut case = None;
+    if let Some(raw) = args.current().and_then(|raw| raw.strip_prefix('#')) {
+        let id = match raw.parse::<u64>() {
+            Ok(id) => id,
+            Err(why) => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <case>: {}", why),
+                )?;
+                return Ok(());
+            }
+        };
+        case = match cases::get(&mut client, id)? {
+            Some(case) => Some(case),
+            None => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Case `#{}` does not exist", id))?;
+                return Ok(());
+            }
+        };
+        args.advance();
+    }
+
+    let reason = args.rest().to_string();
+    if reason.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Argument <reason> not satisfied")?;
+        return Ok(());
+    }
+
+    let config = config::load(&mut client)?;
+    let action = apply(
+        ctx,
+        &mut client,
+        &config,
+        kind,
+        user,
+        msg.author.id,
+        reason,
+        duration,
+        case,
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push(format!("Recorded {} ", kind))
+            .push_mono(format!("#{}", action.id))
+            .push(" against ")
+            .mention(&user)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+// Perform an action against a user and record it against them and the case
+#[allow(clippy::too_many_arguments)]
+pub fn apply(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    kind: ActionKind,
+    user: UserId,
+    moderator: UserId,
+    reason: String,
+    duration: Option<i64>,
+    case: Option<cases::Case>,
+) -> Result<Action, CommandError> {
+    if let (ActionKind::Mute, None) = (kind, config.muted_role) {
+        return Err(CommandError("Muting is not configured".to_string()));
+    }
+
+    // Let the user know before they lose access to the server
+    let description = match (kind, duration) {
+        (ActionKind::Mute, Some(duration)) => format!("muted for {}", format_duration(duration)),
+        (ActionKind::Warn, _) => "warned".to_string(),
+        (ActionKind::Kick, _) => "kicked".to_string(),
+        (ActionKind::Ban, _) => "banned".to_string(),
+        _ => kind.to_string(),
+    };
+    let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            format!(
+                "You have been {} by the moderators. Reason: {}",
+                description, reason
+            ),
+        )
+    });
+    if let Err(e) = sent {
+        error!("Failed to notify user of {}: {}", kind, e);
+    }
+
+    // The everyone role shares its id with the guild
+    let guild_id = GuildId(config.everyone_role.0);
+    match kind {
+        ActionKind::Mute => {
+            if let Some(role) = config.muted_role {
+                ctx.http.add_member_role(guild_id.0, user.0, role.0)?
+            }
+        }
+        ActionKind::Kick => guild_id.kick_with_reason(&ctx.http, user, &reason)?,
+        ActionKind::Ban => {
+            // Discord limits the length of audit log reasons
+            let short = reason.chars().take(500).collect::<String>();
+            guild_id.ban(&ctx.http, user, &(0, short.as_str()))?
+        }
+        _ => {}
+    };
+
+    // Record the action against the user and the case
+    let now = Local::now().timestamp_millis();
+    let action = moderation::record(
+        client,
+        Action {
+            id: 0,
+            kind,
+            user: user.0,
+            moderator: moderator.0,
+            reason,
+            case: case.as_ref().map(|c| c.id),
+            duration,
+            created_at: now,
+        },
+    )?;
+    if let Some(mut case) = case {
+        case.actions.push(action.id);
+        case.updated_at = now;
+        cases::save(client, &case)?;
+    }
+
+    // Lift the mute once it expires
+    if let Some(duration) = duration {
+        moderation::set_mute(client, user.0, action.id)?;
+        scheduler::schedule(
+            client,
+            now + duration,
+            &Job::Unmute {
+                user: user.0,
+                action: action.id,
+            },
+        )?;
+    }
+
+    post_log(ctx, config, &action);
+
+    Ok(action)
+}
+
+// Remove the muted role from a user and record it
+fn lift_mute(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    user: UserId,
+    moderator: UserId,
+    reason: &str,
+) -> CommandResult {
+    let config = config::load(client)?;
+    moderation::clear_mute(client, user.0)?;
+
+    // The user may have left the server in the meantime
+    if let Some(role) = config.muted_role {
+        if let Err(e) = ctx
+            .http
+            .remove_member_role(config.everyone_role.0, user.0, role.0)
+        {
+            error!("Failed to remove muted role from user {}: {}", user.0, e);
+        }
+    }
+
+    let action = moderation::record(
+        client,
+        Action {
+            id: 0,
+            kind: ActionKind::Unmute,
+            user: user.0,
+            moderator: moderator.0,
+            reason: reason.to_string(),
+            case: None,
+            duration: None,
+            created_at: Local::now().timestamp_millis(),
+        },
+    )?;
+    post_log(ctx, &config, &action);
+
+    Ok(())
+}
+
+// Post an entry for an action in the moderation log
+fn post_log(ctx: &Context, config: &config::Config, action: &Action) {
+    let modlog_channel = match config.modlog_channel {
+        Some(channel) => channel,
+        None => return,
+    };
+    let sent = modlog_channel.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title(format!("{} | #{}", action.kind, action.id))
+                .field("User", format!("<@{}>", action.user), true)
+                .field("Moderator", format!("<@{}>", action.moderator), true)
+                .field(
+                    "Case",
+                    match action.case {
+                        Some(case) => format!("#{}", case),
+                        None => "none".to_string(),
+                    },
+                    true,
+                );
+            if let Some(duration) = action.duration {
+                e.field("Duration", format_duration(duration), true);
+            }
+            e.field("Reason", &action.reason, false);
+            if let Some(at) = Utc.timestamp_millis_opt(action.created_at).single() {
+                e.timestamp(&at);
+            }
+            e
+        })
+    });
+
+    if let Err(e) = sent {
+        error!("Failed to post action #{} to the mod log: {}", action.id, e);
+    }
+}
+
+// Parse a user from the args, replying if it is invalid
+fn parse_user(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> Result<Option<UserId>, CommandError> {
+    match args.single::<String>() {
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => Ok(Some(id)),
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <user>: '{}'", raw),
+                )?;
+                Ok(None)
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <user> not satisfied")?;
+            Ok(None)
+        }
+        Err(e) => Err(CommandError(e.to_string())),
+    }
+}
@@ -1,7 +1,14 @@
 use crate::ShardManagerContainer;
-use serenity::framework::standard::{macros::command, CommandResult};
+use chrono::prelude::*;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
+use serenity::utils::{parse_channel, parse_role, MessageBuilder};
+use std::sync::Arc;
+
+use crate::commands::automod;
+use crate::data::{backup, config, get_connection, migrations};
+use crate::util::random_color;
 
 #[command]
 #[help_available(false)]
@@ -21,3 +28,697 @@ pub fn shutdown(ctx: &mut Context, msg: &Message) -> CommandResult {
 
     Ok(())
 }
+
+#[command]
+#[help_available(false)]
+#[description = "View and modify the bot's configuration"]
+#[usage = "<get|set|list>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(config_get, config_set, config_list)]
+pub fn config(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin config get <name>`, `~admin config set <name>, <id>`, or `~admin config list`",
+    )?;
+
+    Ok(())
+}
+
+#[command("get")]
+#[help_available(false)]
+#[description = "Get the value of a configuration setting"]
+#[usage = "<name>"]
+#[example = "reports_channel"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn config_get(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get setting name from args
+    let name = match args.single::<String>() {
+        Ok(name) => name,
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <name> not satisfied")?;
+            return Ok(());
+        }
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <name>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Ensure the setting exists
+    let setting = match config::find(&name) {
+        Some(setting) => setting,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Unknown setting '{}'", name))?;
+            return Ok(());
+        }
+    };
+
+    // Retrieve the current value
+    let mut client = get_connection(&ctx.data)?;
+    let value = config::get(&mut client, setting.name)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push_mono(setting.name)
+            .push(" is ")
+            .push(format_value(setting, value))
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("set")]
+#[help_available(false)]
+#[description = "Set the value of a configuration setting after validating it"]
+#[usage = "<name>, <id>"]
+#[example = "reports_channel, 123456"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(2)]
+pub fn config_set(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get setting name from args
+    let name = match args.single::<String>() {
+        Ok(name) => name,
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <name> not satisfied")?;
+            return Ok(());
+        }
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <name>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Get the id from args, allowing mentions
+    let raw = match args.single::<String>() {
+        Ok(raw) => raw,
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <id> not satisfied")?;
+            return Ok(());
+        }
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let id = match parse_id(&raw) {
+        Some(id) => id,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <id>: '{}'", raw),
+            )?;
+            return Ok(());
+        }
+    };
+
+    // Ensure the setting exists
+    let setting = match config::find(&name) {
+        Some(setting) => setting,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Unknown setting '{}'", name))?;
+            return Ok(());
+        }
+    };
+
+    // Check the id against the server
+    if let Err(why) = config::validate(ctx, msg.guild_id.unwrap(), setting, id) {
+        msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push("Invalid value for ")
+                .push_mono(setting.name)
+                .push(": ")
+                .push(why)
+                .build(),
+        )?;
+        return Ok(());
+    }
+
+    // Persist the new value
+    let mut client = get_connection(&ctx.data)?;
+    config::set(&mut client, setting.name, id)?;
+
+    // Silenced roles only work through a deny in every channel
+    if config::SILENCED_ROLES.contains(&setting.name) {
+        let silenced = PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(RoleId(id)),
+            allow: Permissions::empty(),
+            deny: config::SILENCED,
+        };
+        for channel in msg.guild_id.unwrap().channels(&ctx.http)?.keys() {
+            channel.create_permission(&ctx.http, &silenced)?;
+        }
+    }
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Successfully set ")
+            .push_mono(setting.name)
+            .push(" to ")
+            .push(format_value(setting, Some(id)))
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available(false)]
+#[description = "List all the configuration settings and whether they are valid"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn config_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    // Retrieve all the current values
+    let mut client = get_connection(&ctx.data)?;
+    let values = config::all(&mut client)?;
+
+    let guild_id = msg.guild_id.unwrap();
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.description("Here is the current configuration:");
+
+            for setting in config::SETTINGS {
+                let value = values.get(setting.name).cloned();
+                let status = match value {
+                    Some(id) => match config::validate(ctx, guild_id, setting, id) {
+                        Ok(_) => "Valid".to_string(),
+                        Err(why) => format!("Invalid: {}", why),
+                    },
+                    None if setting.required => "Not set".to_string(),
+                    None => "Not set, this feature is turned off".to_string(),
+                };
+
+                e.field(
+                    setting.name,
+                    format!(
+                        "**Value**: {}\n**Status**: {}\n**Description**: {}",
+                        format_value(setting, value),
+                        status,
+                        setting.description
+                    ),
+                    false,
+                );
+            }
+
+            e
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Create any missing roles and channels and record them in the configuration. This is safe to run multiple times."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn setup(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    // Retrieve the current configuration
+    let mut client = get_connection(&ctx.data)?;
+    let current = config::all(&mut client)?;
+    let mut log = Vec::new();
+
+    // Find the bot's managed role
+    let bot_role = {
+        let bot_id = ctx.cache.read().user.id;
+        let guild = match guild_id.to_guild_cached(&ctx.cache) {
+            Some(guild) => guild,
+            None => {
+                msg.channel_id
+                    .say(&ctx.http, "The server is not available, try again later")?;
+                return Ok(());
+            }
+        };
+        let guild = guild.read();
+
+        guild.members.get(&bot_id).and_then(|m| {
+            m.roles
+                .iter()
+                .filter_map(|r| guild.roles.get(r))
+                .find(|r| r.managed)
+                .map(|r| r.id)
+        })
+    };
+    let bot_role = match bot_role {
+        Some(role) => role,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Unable to find the bot's role in this server")?;
+            return Ok(());
+        }
+    };
+
+    // Ensure all the roles exist
+    let member_permissions = Permissions::CHANGE_NICKNAME
+        | Permissions::READ_MESSAGES
+        | Permissions::SEND_MESSAGES
+        | Permissions::EMBED_LINKS
+        | Permissions::ATTACH_FILES
+        | Permissions::READ_MESSAGE_HISTORY
+        | Permissions::USE_EXTERNAL_EMOJIS
+        | Permissions::ADD_REACTIONS
+        | Permissions::CONNECT
+        | Permissions::SPEAK
+        | Permissions::USE_VAD;
+    let teamless_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("teamless_role"),
+        "Teamless",
+        member_permissions,
+        &mut log,
+    )?;
+    let mentor_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("mentor_role"),
+        "Mentor",
+        member_permissions,
+        &mut log,
+    )?;
+    let judge_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("judge_role"),
+        "Judge",
+        member_permissions,
+        &mut log,
+    )?;
+    let manager_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("manager_role"),
+        "Manager",
+        member_permissions
+            | Permissions::MANAGE_MESSAGES
+            | Permissions::MANAGE_NICKNAMES
+            | Permissions::KICK_MEMBERS
+            | Permissions::BAN_MEMBERS
+            | Permissions::MUTE_MEMBERS,
+        &mut log,
+    )?;
+    let muted_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("muted_role"),
+        "Muted",
+        Permissions::empty(),
+        &mut log,
+    )?;
+    let quarantine_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("quarantine_role"),
+        "Quarantine",
+        Permissions::empty(),
+        &mut log,
+    )?;
+    let unverified_role = ensure_role(
+        ctx,
+        guild_id,
+        current.get("unverified_role"),
+        "Unverified",
+        Permissions::empty(),
+        &mut log,
+    )?;
+
+    // Ensure all the channels exist with the correct permissions
+    let everyone_role = RoleId(guild_id.0);
+    let private = |roles: &[RoleId]| {
+        let mut overwrites = vec![PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(everyone_role),
+            allow: Permissions::empty(),
+            deny: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+        }];
+        overwrites.extend(roles.iter().map(|role| PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(*role),
+            allow: Permissions::READ_MESSAGES
+                | Permissions::READ_MESSAGE_HISTORY
+                | Permissions::SEND_MESSAGES,
+            deny: Permissions::empty(),
+        }));
+        overwrites
+    };
+    let mut tables_overwrites = private(&[manager_role]);
+    tables_overwrites.push(PermissionOverwrite {
+        kind: PermissionOverwriteType::Role(bot_role),
+        allow: Permissions::READ_MESSAGES
+            | Permissions::READ_MESSAGE_HISTORY
+            | Permissions::SEND_MESSAGES
+            | Permissions::MANAGE_CHANNELS
+            | Permissions::MANAGE_ROLES,
+        deny: Permissions::empty(),
+    });
+    let tables_category = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("tables_category"),
+        "Tables",
+        ChannelType::Category,
+        tables_overwrites,
+        &mut log,
+    )?;
+    let reports_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("reports_channel"),
+        "reports",
+        ChannelType::Text,
+        private(&[manager_role, mentor_role, bot_role]),
+        &mut log,
+    )?;
+    let mentors_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("mentors_channel"),
+        "mentors",
+        ChannelType::Text,
+        private(&[manager_role, mentor_role, bot_role]),
+        &mut log,
+    )?;
+    let modlog_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("modlog_channel"),
+        "mod-log",
+        ChannelType::Text,
+        private(&[manager_role, bot_role]),
+        &mut log,
+    )?;
+    let audit_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("audit_channel"),
+        "audit-log",
+        ChannelType::Text,
+        private(&[manager_role, bot_role]),
+        &mut log,
+    )?;
+
+    let gate_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("gate_channel"),
+        "code-of-conduct",
+        ChannelType::Text,
+        vec![
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(everyone_role),
+                allow: Permissions::READ_MESSAGES
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::ADD_REACTIONS,
+                deny: Permissions::SEND_MESSAGES,
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(bot_role),
+                allow: Permissions::READ_MESSAGES
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::SEND_MESSAGES,
+                deny: Permissions::empty(),
+            },
+        ],
+        &mut log,
+    )?;
+    let announcements_channel = ensure_channel(
+        ctx,
+        guild_id,
+        current.get("announcements_channel"),
+        "announcements",
+        ChannelType::Text,
+        vec![
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(everyone_role),
+                allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::SEND_MESSAGES,
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(bot_role),
+                allow: Permissions::READ_MESSAGES
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::SEND_MESSAGES
+                    | Permissions::MENTION_EVERYONE,
+                deny: Permissions::empty(),
+            },
+        ],
+        &mut log,
+    )?;
+
+    // Prevent muted, quarantined, and unverified users from talking anywhere
+    let channels = guild_id.channels(&ctx.http)?;
+    for role in &[muted_role, quarantine_role, unverified_role] {
+        let silenced = PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(*role),
+            allow: Permissions::empty(),
+            deny: config::SILENCED,
+        };
+        for channel in channels.keys() {
+            channel.create_permission(&ctx.http, &silenced)?;
+        }
+    }
+    log.push(format!(
+        "Denied the muted, quarantine, and unverified roles from talking in {} channels",
+        channels.len()
+    ));
+
+    // Record everything in the configuration
+    config::set(&mut client, "everyone_role", everyone_role.0)?;
+    config::set(&mut client, "bot_role", bot_role.0)?;
+    config::set(&mut client, "teamless_role", teamless_role.0)?;
+    config::set(&mut client, "mentor_role", mentor_role.0)?;
+    config::set(&mut client, "judge_role", judge_role.0)?;
+    config::set(&mut client, "manager_role", manager_role.0)?;
+    config::set(&mut client, "muted_role", muted_role.0)?;
+    config::set(&mut client, "quarantine_role", quarantine_role.0)?;
+    config::set(&mut client, "unverified_role", unverified_role.0)?;
+    config::set(&mut client, "tables_category", tables_category.0)?;
+    config::set(&mut client, "reports_channel", reports_channel.0)?;
+    config::set(&mut client, "mentors_channel", mentors_channel.0)?;
+    config::set(&mut client, "modlog_channel", modlog_channel.0)?;
+    config::set(&mut client, "audit_channel", audit_channel.0)?;
+    config::set(&mut client, "gate_channel", gate_channel.0)?;
+    config::set(
+        &mut client,
+        "announcements_channel",
+        announcements_channel.0,
+    )?;
+
+    // Send a summary of the changes
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Setup complete")
+                .description(log.join("\n"))
+                .footer(|f| f.text("Use `~admin config list` to review the configuration"))
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Export a snapshot of all the bot's data. The snapshot is sent through a direct message."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn export(ctx: &mut Context, msg: &Message) -> CommandResult {
+    // Create the snapshot
+    let mut client = get_connection(&ctx.data)?;
+    let snapshot = backup::export(&mut client)?;
+    let raw = serde_json::to_vec_pretty(&snapshot)?;
+
+    // Send it privately as it may contain sensitive information
+    let filename = format!("svh-bot-{}.json", Utc::now().format("%Y%m%d-%H%M%S"));
+    msg.author.create_dm_channel(&ctx.http)?.send_files(
+        &ctx.http,
+        vec![(raw.as_slice(), filename.as_str())],
+        |m| {
+            m.content(format!(
+                "Snapshot of {} keys (version {})",
+                snapshot.keys.len(),
+                snapshot.version
+            ))
+        },
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Sent the snapshot to ")
+            .mention(&msg.author)
+            .push(" through a direct message.")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Restore a snapshot created by `~admin export`. The snapshot must be attached to the message and the database must not have any other data."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn import(ctx: &mut Context, msg: &Message) -> CommandResult {
+    // Retrieve the attached snapshot
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please attach a snapshot to the message")?;
+            return Ok(());
+        }
+    };
+    let snapshot = match backup::parse(&attachment.download()?) {
+        Ok(snapshot) => snapshot,
+        Err(why) => {
+            msg.channel_id.say(&ctx.http, why)?;
+            return Ok(());
+        }
+    };
+
+    // Refuse to overwrite existing data
+    let mut client = get_connection(&ctx.data)?;
+    if !backup::is_empty(&mut client)? {
+        msg.channel_id
+            .say(&ctx.http, "The database must be empty to import a snapshot")?;
+        return Ok(());
+    }
+
+    // Bring older snapshots up to date and fill in settings they're missing
+    let count = backup::import(&mut client, &snapshot)?;
+    migrations::run(&mut client, false)?;
+    config::seed(&mut client)?;
+    ctx.data.write().remove::<automod::CompiledRules>();
+    msg.channel_id
+        .say(&ctx.http, format!("Successfully restored {} keys.", count))?;
+
+    Ok(())
+}
+
+// Find a role by its configured id or name, creating it if it doesn't exist
+fn ensure_role(
+    ctx: &Context,
+    guild_id: GuildId,
+    configured: Option<&u64>,
+    name: &str,
+    permissions: Permissions,
+    log: &mut Vec<String>,
+) -> serenity::Result<RoleId> {
+    let existing = guild_id.to_guild_cached(&ctx.cache).and_then(|guild| {
+        let guild = guild.read();
+        configured
+            .and_then(|id| guild.roles.get(&RoleId(*id)))
+            .or_else(|| guild.role_by_name(name))
+            .map(|role| role.id)
+    });
+
+    if let Some(role) = existing {
+        log.push(format!("Using existing role <@&{}>", role.0));
+        return Ok(role);
+    }
+
+    let role = guild_id.create_role(&ctx.http, |r| {
+        r.name(name)
+            .colour(random_color().0.into())
+            .mentionable(true)
+            .hoist(true)
+            .permissions(permissions)
+    })?;
+    log.push(format!("Created role <@&{}>", role.id.0));
+
+    Ok(role.id)
+}
+
+// Find a channel by its configured id or name, creating it if it doesn't exist.
+// The permission overwrites are reapplied to existing channels to repair them.
+fn ensure_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    configured: Option<&u64>,
+    name: &str,
+    kind: ChannelType,
+    overwrites: Vec<PermissionOverwrite>,
+    log: &mut Vec<String>,
+) -> serenity::Result<ChannelId> {
+    let existing = guild_id.to_guild_cached(&ctx.cache).and_then(|guild| {
+        let guild = guild.read();
+        let matches = |c: &&Arc<RwLock<GuildChannel>>| c.read().kind == kind;
+
+        configured
+            .and_then(|id| guild.channels.get(&ChannelId(*id)))
+            .filter(matches)
+            .or_else(|| {
+                guild
+                    .channels
+                    .values()
+                    .filter(matches)
+                    .find(|c| c.read().name.eq_ignore_ascii_case(name))
+            })
+            .map(|c| c.read().id)
+    });
+
+    if let Some(channel) = existing {
+        for overwrite in &overwrites {
+            channel.create_permission(&ctx.http, overwrite)?;
+        }
+        log.push(format!(
+            "Using existing {} `{}` and repaired its permissions",
+            kind.name(),
+            channel.0
+        ));
+        return Ok(channel);
+    }
+
+    let channel = guild_id.create_channel(&ctx.http, |c| {
+        c.name(name).kind(kind).permissions(overwrites)
+    })?;
+    log.push(format!("Created {} `{}`", kind.name(), channel.id.0));
+
+    Ok(channel.id)
+}
+
+// Parse an id from either a raw number or a mention
+fn parse_id(raw: &str) -> Option<u64> {
+    raw.parse::<u64>()
+        .ok()
+        .or_else(|| parse_channel(raw))
+        .or_else(|| parse_role(raw))
+}
+
+// Format a setting's value as a mention
+fn format_value(setting: &config::Setting, value: Option<u64>) -> String {
+    match (setting.kind, value) {
+        (_, None) => "not set".to_string(),
+        (config::Kind::Category, Some(id)) => format!("`{}`", id),
+        (config::Kind::TextChannel, Some(id)) => format!("<#{}>", id),
+        (_, Some(id)) if setting.name == "everyone_role" => format!("`{}`", id),
+        (_, Some(id)) => format!("<@&{}>", id),
+    }
+}
@@ -0,0 +1,421 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::data::{
+    agenda::{self, Item},
+    config, event, get_connection, workshops,
+};
+use crate::scheduler::{self, Job};
+use crate::util::{format_duration, format_time, parse_time};
+
+// How long before an item starts reminders are sent
+const REMINDER_LEAD: i64 = 10 * 60_000;
+
+// Most items that fit in an embed
+const MAX_ITEMS: usize = 25;
+
+#[command]
+#[help_available]
+#[description = "Show the upcoming items on the event schedule"]
+#[num_args(0)]
+#[sub_commands(schedule_now, schedule_next, schedule_remind)]
+pub fn schedule(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let now = Utc::now().timestamp_millis();
+    show(ctx, msg, "Schedule", "Nothing else is scheduled", |items| {
+        items
+            .into_iter()
+            .filter(|i| i.end > now)
+            .take(MAX_ITEMS)
+            .collect()
+    })
+}
+
+#[command("now")]
+#[help_available]
+#[description = "Show what is happening right now"]
+#[num_args(0)]
+pub fn schedule_now(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let now = Utc::now().timestamp_millis();
+    show(
+        ctx,
+        msg,
+        "Happening now",
+        "Nothing is happening right now",
+        |items| {
+            items
+                .into_iter()
+                .filter(|i| i.start <= now && i.end > now)
+                .take(MAX_ITEMS)
+                .collect()
+        },
+    )
+}
+
+#[command("next")]
+#[help_available]
+#[description = "Show what is happening next"]
+#[num_args(0)]
+pub fn schedule_next(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let now = Utc::now().timestamp_millis();
+    show(ctx, msg, "Up next", "Nothing else is scheduled", |items| {
+        // Include everything starting at the same time
+        let next = match items.iter().find(|i| i.start > now) {
+            Some(item) => item.start,
+            None => return Vec::new(),
+        };
+        items
+            .into_iter()
+            .filter(|i| i.start == next)
+            .take(MAX_ITEMS)
+            .collect()
+    })
+}
+
+#[command("remind")]
+#[help_available]
+#[description = "Get a direct message before an item on the schedule starts. Run it again to stop being reminded."]
+#[usage = "<id>"]
+#[example = "4"]
+#[num_args(1)]
+pub fn schedule_remind(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let item = match parse_item(ctx, msg, &mut args, &mut client)? {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+
+    let reply = if agenda::toggle_reminder(&mut client, item.id, msg.author.id.0)? {
+        format!(
+            "You'll get a direct message {} before **{}** starts.",
+            format_duration(REMINDER_LEAD),
+            item.title
+        )
+    } else {
+        format!("You won't be reminded about **{}** anymore.", item.title)
+    };
+    msg.channel_id.say(&ctx.http, reply)?;
+
+    Ok(())
+}
+
+#[command("agenda")]
+#[help_available(false)]
+#[description = "Manage the items on the event schedule"]
+#[usage = "<add|remove|import>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(agenda_add, agenda_remove, agenda_import)]
+pub fn agenda_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin agenda add <title>, <start>, <end>, <location>, [<description>]`, `~admin agenda remove <id>`, or `~admin agenda import` (with the CSV attached)",
+    )?;
+
+    Ok(())
+}
+
+#[command("add")]
+#[help_available(false)]
+#[description = "Add an item to the schedule. Times are in the event timezone."]
+#[usage = "<title>, <start>, <end>, <location>, [<description>]"]
+#[example = "Intro to Rust, 2020-10-24 13:00, 2020-10-24 14:00, #workshops, Learn the basics of Rust"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(4)]
+pub fn agenda_add(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let now = Utc::now().timestamp_millis();
+
+    // Get the item from args
+    let title = args.single::<String>()?.trim().to_string();
+    let mut times = Vec::new();
+    for name in &["start", "end"] {
+        let raw = args.single::<String>()?;
+        match parse_time(&raw, now, timezone) {
+            Some(time) => times.push(time),
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <{}>: '{}' must be like `in 2h` or `2020-10-24 13:00`",
+                        name, raw
+                    ),
+                )?;
+                return Ok(());
+            }
+        }
+    }
+    if times[0] >= times[1] {
+        msg.channel_id
+            .say(&ctx.http, "The item must start before it ends")?;
+        return Ok(());
+    }
+    let location = args.single::<String>()?.trim().to_string();
+    let description = args.rest().trim().to_string();
+
+    let item = agenda::add(
+        &mut client,
+        Item {
+            id: 0,
+            title,
+            start: times[0],
+            end: times[1],
+            location,
+            description,
+        },
+    )?;
+    schedule_reminder(&mut client, &item)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!(
+            "Added **{}** to the schedule as `#{}` starting {}.",
+            item.title,
+            item.id,
+            format_time(item.start, timezone)
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[help_available(false)]
+#[description = "Remove an item from the schedule. Its workshop must be closed first."]
+#[usage = "<id>"]
+#[example = "4"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn agenda_remove(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let item = match parse_item(ctx, msg, &mut args, &mut client)? {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+
+    // Workshops are keyed by their item, so they must be closed first
+    if workshops::get(&mut client, item.id)?.is_some() {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "**{}** has a workshop, close it first with `~admin workshop remove {}`",
+                item.title, item.id
+            ),
+        )?;
+        return Ok(());
+    }
+
+    agenda::remove(&mut client, item.id)?;
+    scheduler::cancel(&mut client, &Job::Remind { item: item.id })?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Removed **{}** from the schedule.", item.title),
+    )?;
+
+    Ok(())
+}
+
+#[command("import")]
+#[help_available(false)]
+#[description = "Replace the schedule with an attached CSV with the columns title, start, end, location, and description. Times are in the event timezone. Any workshops must be closed first."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn agenda_import(ctx: &mut Context, msg: &Message) -> CommandResult {
+    // Retrieve the attached agenda
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please attach the agenda to the message")?;
+            return Ok(());
+        }
+    };
+    let raw = String::from_utf8_lossy(&attachment.download()?).to_string();
+
+    // Importing replaces every item, which would orphan their workshops
+    let mut client = get_connection(&ctx.data)?;
+    let mut open = workshops::all(&mut client)?
+        .iter()
+        .map(|w| w.id)
+        .collect::<Vec<_>>();
+    if !open.is_empty() {
+        open.sort_unstable();
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "Items {} have workshops, close them first with `~admin workshop remove <id>`",
+                open.iter()
+                    .map(|id| format!("`#{}`", id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )?;
+        return Ok(());
+    }
+
+    let timezone = event::timezone(&mut client)?;
+    let items = match agenda::parse(&raw, timezone, Utc::now().timestamp_millis()) {
+        Ok(items) => items,
+        Err(why) => {
+            msg.channel_id.say(&ctx.http, why)?;
+            return Ok(());
+        }
+    };
+
+    // Remove the previous schedule along with its reminders
+    for item in agenda::all(&mut client)? {
+        agenda::remove(&mut client, item.id)?;
+        scheduler::cancel(&mut client, &Job::Remind { item: item.id })?;
+    }
+
+    let count = items.len();
+    for item in items {
+        let item = agenda::add(&mut client, item)?;
+        schedule_reminder(&mut client, &item)?;
+    }
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Imported {} items into the schedule.", count),
+    )?;
+
+    Ok(())
+}
+
+// Announce that an item is starting soon and remind everyone that asked
+pub fn remind(ctx: &Context, id: u64) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let item = match agenda::get(&mut client, id)? {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+    let config = config::load(&mut client)?;
+    let timezone = event::timezone(&mut client)?;
+
+    let title = format!("Starting soon: {}", item.title);
+    if let Some(channel) = config.announcements_channel {
+        channel.send_message(&ctx.http, |m| {
+            m.embed(|e| describe(e, &item, &title, timezone))
+        })?;
+    }
+
+    for user in agenda::reminders(&mut client, id)? {
+        let sent = UserId(user).create_dm_channel(&ctx.http).and_then(|c| {
+            c.send_message(&ctx.http, |m| {
+                m.embed(|e| describe(e, &item, &title, timezone))
+            })
+        });
+        if let Err(e) = sent {
+            error!("Failed to send reminder for agenda item {}: {}", id, e);
+        }
+    }
+
+    Ok(())
+}
+
+// Show the items on the schedule selected by a filter
+fn show(
+    ctx: &Context,
+    msg: &Message,
+    title: &str,
+    empty: &str,
+    filter: impl FnOnce(Vec<Item>) -> Vec<Item>,
+) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let items = filter(agenda::all(&mut client)?);
+    if items.is_empty() {
+        msg.channel_id.say(&ctx.http, empty)?;
+        return Ok(());
+    }
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title(title);
+            for item in &items {
+                e.field(
+                    format!("{} (`#{}`)", item.title, item.id),
+                    format!("{}\n{}", times(item, timezone), item.location),
+                    false,
+                );
+            }
+            e.footer(|f| {
+                f.text(format!(
+                    "Times are in {}. Use `~schedule remind <id>` to get a reminder.",
+                    timezone.name()
+                ))
+            })
+        })
+    })?;
+
+    Ok(())
+}
+
+// Describe a single item in an embed
+fn describe<'a>(
+    e: &'a mut serenity::builder::CreateEmbed,
+    item: &Item,
+    title: &str,
+    timezone: Tz,
+) -> &'a mut serenity::builder::CreateEmbed {
+    e.title(title)
+        .field("When", times(item, timezone), true)
+        .field("Where", &item.location, true);
+    if !item.description.is_empty() {
+        e.description(&item.description);
+    }
+    e
+}
+
+// Format when an item starts and ends
+fn times(item: &Item, timezone: Tz) -> String {
+    let end = match timezone.timestamp_millis_opt(item.end).single() {
+        Some(end) => end.format("%-I:%M %p %Z").to_string(),
+        None => "an unknown time".to_string(),
+    };
+    format!("{} to {}", format_time(item.start, timezone), end)
+}
+
+// Schedule the reminder for an item if it hasn't passed
+fn schedule_reminder(client: &mut redis::Connection, item: &Item) -> CommandResult {
+    let at = item.start - REMINDER_LEAD;
+    if at > Utc::now().timestamp_millis() {
+        scheduler::schedule(client, at, &Job::Remind { item: item.id })?;
+    }
+    Ok(())
+}
+
+// Get an item from args, replying if it is invalid
+fn parse_item(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+    client: &mut redis::Connection,
+) -> Result<Option<Item>, CommandError> {
+    let id = match args.single::<u64>() {
+        Ok(id) => id,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(None);
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    match agenda::get(client, id)? {
+        Some(item) => Ok(Some(item)),
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Item `#{}` is not on the schedule", id))?;
+            Ok(None)
+        }
+    }
+}
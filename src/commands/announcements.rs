@@ -0,0 +1,330 @@
+use chrono::prelude::*;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::{parse_channel, MessageBuilder};
+
+use crate::data::{
+    announcements::{self, Announcement, Timing},
+    event, get_connection,
+};
+use crate::scheduler::{self, Job};
+use crate::util::{format_duration, format_time, parse_duration, parse_time, truncate};
+
+// Message posted by countdowns without their own
+const DEFAULT_COUNTDOWN: &str = "{remaining} left until the end of the event!";
+
+#[command]
+#[help_available(false)]
+#[description = "Schedule messages to be posted later"]
+#[usage = "<schedule|countdown|list|cancel>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(announce_schedule, announce_countdown, announce_list, announce_cancel)]
+pub fn announce(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin announce schedule <time>, <channel>, <message>`, `~admin announce countdown <channel>, <every>, <starting>, [<message>]`, `~admin announce list`, or `~admin announce cancel <id>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("schedule")]
+#[help_available(false)]
+#[description = "Post a message in a channel at a time. The time is either relative or a date and time in the event timezone."]
+#[usage = "<time>, <channel>, <message>"]
+#[example = "2020-10-24 21:00, #general, Dinner is being served!"]
+#[example = "in 30m, #general, The opening ceremony starts soon"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(3)]
+pub fn announce_schedule(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+
+    // Get the time, channel, and message from args
+    let now = Utc::now().timestamp_millis();
+    let raw = args.single::<String>()?;
+    let at = match parse_time(&raw, now, timezone) {
+        Some(at) if at > now => at,
+        _ => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <time>: '{}' must be in the future, like `in 2h` or `2020-10-25 12:00`",
+                    raw
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+    let channel = match parse_channel_arg(ctx, msg, &mut args)? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    let message = args.rest().trim().to_string();
+
+    let announcement = announcements::create(&mut client, channel.0, message, Timing::Once { at })?;
+    scheduler::schedule(
+        &mut client,
+        at,
+        &Job::Announce {
+            id: announcement.id,
+        },
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push(format!("Scheduled announcement `#{}` in ", announcement.id))
+            .channel(channel)
+            .push(format!(" for {}.", format_time(at, timezone)))
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("countdown")]
+#[help_available(false)]
+#[description = "Repeatedly post how much time is left before the end of the event, starting some time before it. The message can include `{remaining}` for the time left."]
+#[usage = "<channel>, <every>, <starting>, [<message>]"]
+#[example = "#general, 1h, 6h"]
+#[example = "#general, 30m, 2h, Only {remaining} left, remember to submit!"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(3)]
+pub fn announce_countdown(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the channel, interval, and start from args
+    let channel = match parse_channel_arg(ctx, msg, &mut args)? {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    let mut durations = Vec::new();
+    for name in &["every", "starting"] {
+        let raw = args.single::<String>()?;
+        match parse_duration(&raw) {
+            Some(duration) if duration >= 60_000 => durations.push(duration),
+            _ => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <{}>: '{}' must be a duration of at least a minute, like `1h`",
+                        name, raw
+                    ),
+                )?;
+                return Ok(());
+            }
+        }
+    }
+    let (every, starting) = (durations[0], durations[1]);
+    let message = match args.rest().trim() {
+        "" => DEFAULT_COUNTDOWN.to_string(),
+        message => message.to_string(),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let announcement = announcements::create(
+        &mut client,
+        channel.0,
+        message,
+        Timing::Countdown { every, starting },
+    )?;
+    let timezone = event::timezone(&mut client)?;
+    let next = schedule_next(&mut client, &announcement)?;
+
+    let mut reply = MessageBuilder::new();
+    reply
+        .push(format!("Scheduled countdown `#{}` in ", announcement.id))
+        .channel(channel)
+        .push(format!(
+            " every {} starting {} before the end of the event",
+            format_duration(every),
+            format_duration(starting)
+        ));
+    match next {
+        Some(next) => reply.push(format!(", first posting {}.", format_time(next, timezone))),
+        None => reply.push(". It won't be posted until the end of the event is set with `~admin event end <time>`."),
+    };
+    msg.channel_id.say(&ctx.http, reply.build())?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available(false)]
+#[description = "List all the scheduled announcements and countdowns"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn announce_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let all = announcements::all(&mut client)?;
+    if all.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "There are no scheduled announcements")?;
+        return Ok(());
+    }
+
+    let timezone = event::timezone(&mut client)?;
+    let end = event::end(&mut client)?;
+    let now = Utc::now().timestamp_millis();
+    let lines = all
+        .iter()
+        .map(|a| {
+            let when = match a.timing {
+                Timing::Once { at } => format_time(at, timezone),
+                Timing::Countdown { every, starting } => format!(
+                    "every {} starting {} before the end, next {}",
+                    format_duration(every),
+                    format_duration(starting),
+                    match a.next(now, end) {
+                        Some(next) => format_time(next, timezone),
+                        None => "never".to_string(),
+                    }
+                ),
+            };
+            format!(
+                "`#{}` in <#{}> {}: {}",
+                a.id,
+                a.channel,
+                when,
+                truncate(&a.message, 100)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Scheduled announcements")
+                .description(lines.join("\n"))
+                .footer(|f| f.text(format!("Times are in {}", timezone.name())))
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("cancel")]
+#[help_available(false)]
+#[description = "Cancel a scheduled announcement or countdown"]
+#[usage = "<id>"]
+#[example = "3"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn announce_cancel(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = match args.single::<u64>() {
+        Ok(id) => id,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if !announcements::remove(&mut client, id)? {
+        msg.channel_id
+            .say(&ctx.http, format!("Announcement `#{}` does not exist", id))?;
+        return Ok(());
+    }
+    scheduler::cancel(&mut client, &Job::Announce { id })?;
+
+    msg.channel_id
+        .say(&ctx.http, format!("Cancelled announcement `#{}`.", id))?;
+
+    Ok(())
+}
+
+// Post a scheduled announcement, scheduling the next one for countdowns
+pub fn post(ctx: &Context, id: u64) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let announcement = match announcements::get(&mut client, id)? {
+        Some(announcement) => announcement,
+        None => return Ok(()),
+    };
+
+    let message = match (announcement.timing, event::end(&mut client)?) {
+        (Timing::Countdown { .. }, Some(end)) => {
+            // Jobs run slightly late, so round to the nearest minute
+            let remaining = (end - Utc::now().timestamp_millis() + 30_000) / 60_000 * 60_000;
+            announcement
+                .message
+                .replace("{remaining}", &format_duration(remaining.max(60_000)))
+        }
+        _ => announcement.message.clone(),
+    };
+    ChannelId(announcement.channel).say(&ctx.http, message)?;
+
+    if schedule_next(&mut client, &announcement)?.is_none() {
+        announcements::remove(&mut client, id)?;
+    }
+
+    Ok(())
+}
+
+// Schedule every countdown again after the end of the event changes
+pub fn reschedule_countdowns(client: &mut redis::Connection) -> CommandResult {
+    for announcement in announcements::all(client)? {
+        if let Timing::Countdown { .. } = announcement.timing {
+            scheduler::cancel(
+                client,
+                &Job::Announce {
+                    id: announcement.id,
+                },
+            )?;
+            schedule_next(client, &announcement)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Schedule the next time an announcement is posted, returning when that is
+fn schedule_next(
+    client: &mut redis::Connection,
+    announcement: &Announcement,
+) -> Result<Option<i64>, CommandError> {
+    let next = announcement.next(Utc::now().timestamp_millis(), event::end(client)?);
+    if let Some(at) = next {
+        scheduler::schedule(
+            client,
+            at,
+            &Job::Announce {
+                id: announcement.id,
+            },
+        )?;
+    }
+    Ok(next)
+}
+
+// Get a channel from args, replying if it is invalid
+fn parse_channel_arg(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> Result<Option<ChannelId>, CommandError> {
+    let raw = match args.single::<String>() {
+        Ok(raw) => raw,
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <channel> not satisfied")?;
+            return Ok(None);
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    match parse_channel(&raw).or_else(|| raw.parse::<u64>().ok()) {
+        Some(id) => Ok(Some(ChannelId(id))),
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <channel>: '{}'", raw),
+            )?;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use chrono::prelude::*;
+use redis::Commands;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::commands::checks::ACCEPTED_CHECK;
+use crate::data::{attendance, get_connection};
+use crate::util::{format_duration, parse_duration};
+
+#[command]
+#[help_available]
+#[checks(Accepted)]
+#[description = "Check in to the event while check-in is open. This is used to confirm you participated."]
+#[num_args(0)]
+pub fn checkin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let window = match attendance::current(&mut client)? {
+        Some((window, _)) => window,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Check-in isn't open right now")?;
+            return Ok(());
+        }
+    };
+
+    let checked_in = attendance::check_in(
+        &mut client,
+        &window,
+        msg.author.id.0,
+        Utc::now().timestamp_millis(),
+    )?;
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .mention(&msg.author)
+            .push(if checked_in {
+                " You're checked in for "
+            } else {
+                " You've already checked in for "
+            })
+            .push_mono(&window)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("checkin")]
+#[help_available(false)]
+#[description = "Open or close a check-in window"]
+#[usage = "<open|close>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(checkin_open, checkin_close)]
+pub fn checkin_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin checkin open <window>, <duration>` or `~admin checkin close`",
+    )?;
+
+    Ok(())
+}
+
+#[command("open")]
+#[help_available(false)]
+#[description = "Open a check-in window, such as kickoff, midpoint, or closing, for a duration. Any open window is replaced."]
+#[usage = "<window>, <duration>"]
+#[example = "kickoff, 30m"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(2)]
+pub fn checkin_open(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the window and duration from args
+    let window = args.single::<String>()?.trim().to_lowercase();
+    let duration = match args.single::<String>() {
+        Ok(raw) => match parse_duration(&raw) {
+            Some(duration) if duration >= 1000 => duration,
+            _ => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <duration>: '{}' must be a duration like `30m`",
+                        raw
+                    ),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <duration> not satisfied")?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    attendance::open(
+        &mut client,
+        &window,
+        (duration / 1000) as usize,
+        Utc::now().timestamp_millis(),
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Check-in for ")
+            .push_mono(&window)
+            .push(format!(
+                " is open for {}. Participants can check in with `~checkin`.",
+                format_duration(duration)
+            ))
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("close")]
+#[help_available(false)]
+#[description = "Close the current check-in window early"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn checkin_close(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    match attendance::close(&mut client)? {
+        Some(window) => msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push("Closed check-in for ")
+                .push_mono(window)
+                .push(".")
+                .build(),
+        )?,
+        None => msg
+            .channel_id
+            .say(&ctx.http, "Check-in isn't open right now")?,
+    };
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Report who checked in during each window. A summary is posted and the full list is sent as a CSV through a direct message."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn attendance(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let windows = attendance::windows(&mut client)?;
+    if windows.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Check-in hasn't been opened yet")?;
+        return Ok(());
+    }
+
+    let tables: HashMap<u64, String> = client.hgetall("tables")?;
+    let mut attendees = Vec::new();
+    for window in &windows {
+        attendees.push(attendance::attendees(&mut client, window)?);
+    }
+    let current = attendance::current(&mut client)?;
+
+    // Summarize each window
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Attendance");
+            for (window, users) in windows.iter().zip(&attendees) {
+                let on_table = users.keys().filter(|u| tables.contains_key(u)).count();
+                let mut name = window.clone();
+                if let Some((open, remaining)) = &current {
+                    if open == window {
+                        name.push_str(&format!(
+                            " (open for {})",
+                            format_duration(remaining * 1000)
+                        ));
+                    }
+                }
+                e.field(
+                    name,
+                    format!(
+                        "{} checked in\n{} on a table\n{} teamless",
+                        users.len(),
+                        on_table,
+                        users.len() - on_table
+                    ),
+                    true,
+                );
+            }
+            e
+        })
+    })?;
+
+    // List every participant that checked in at least once
+    let users = attendees
+        .iter()
+        .flat_map(|a| a.keys().copied())
+        .collect::<BTreeSet<_>>();
+    let mut csv = format!("user_id,username,table,{}\n", windows.join(","));
+    for user in &users {
+        let name = UserId(*user)
+            .to_user_cached(&ctx.cache)
+            .map(|u| u.read().tag())
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},\"{}\",{}",
+            user,
+            name.replace('"', "\"\""),
+            tables.get(user).map(String::as_str).unwrap_or("")
+        ));
+        for window in &attendees {
+            csv.push(',');
+            if let Some(at) = window
+                .get(user)
+                .and_then(|at| Utc.timestamp_millis_opt(*at).single())
+            {
+                csv.push_str(&at.to_rfc3339());
+            }
+        }
+        csv.push('\n');
+    }
+
+    // Send it privately as it contains member information
+    let filename = format!("attendance-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    msg.author.create_dm_channel(&ctx.http)?.send_files(
+        &ctx.http,
+        vec![(csv.as_bytes(), filename.as_str())],
+        |m| m.content(format!("{} participants checked in", users.len())),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
+use serenity::model::{event::MessageUpdateEvent, prelude::*};
+use serenity::prelude::*;
+
+use crate::data::{
+    audit::{self, CachedMessage},
+    config, get_connection, modmail,
+};
+use crate::util::truncate;
+
+#[command]
+#[help_available(false)]
+#[description = "Configure which edited and deleted messages are logged"]
+#[usage = "<tables>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(audit_tables)]
+pub fn audit(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id
+        .say(&ctx.http, "Please use `~admin audit tables <on|off>`")?;
+
+    Ok(())
+}
+
+#[command("tables")]
+#[help_available(false)]
+#[description = "Set whether edited and deleted messages in the team channels are logged"]
+#[usage = "<on|off>"]
+#[example = "on"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn audit_tables(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let enabled = match args.single::<String>()?.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        other => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <on|off>: '{}' is not one of on or off",
+                    other
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    audit::set_log_tables(&mut client, enabled)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        if enabled {
+            "Edited and deleted messages in the team channels will now be logged."
+        } else {
+            "Edited and deleted messages in the team channels will no longer be logged."
+        },
+    )?;
+
+    Ok(())
+}
+
+// Remember the content of a new message so later changes can be logged
+pub fn record(ctx: &Context, msg: &Message) -> CommandResult {
+    if msg.author.bot || msg.guild_id.is_none() {
+        return Ok(());
+    }
+
+    // Ticket codes are deleted right away and must never reach the log
+    let command = msg.content.split_whitespace().next().unwrap_or_default();
+    if command.eq_ignore_ascii_case("~verify") {
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    if config.audit_channel.is_none() || !is_logged(ctx, &mut client, &config, msg.channel_id)? {
+        return Ok(());
+    }
+
+    audit::cache(
+        &mut client,
+        msg.id.0,
+        &CachedMessage {
+            author: msg.author.id.0,
+            author_tag: msg.author.tag(),
+            channel: msg.channel_id.0,
+            content: msg.content.clone(),
+            attachments: msg.attachments.iter().map(|a| a.url.clone()).collect(),
+        },
+    )?;
+
+    Ok(())
+}
+
+// Log the previous and new content of an edited message
+pub fn edited(ctx: &Context, event: &MessageUpdateEvent) -> CommandResult {
+    // Embeds being resolved also trigger updates without any new content
+    let content = match &event.content {
+        Some(content) => content,
+        None => return Ok(()),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let mut cached = match audit::get(&mut client, event.id.0)? {
+        Some(cached) => cached,
+        None => return Ok(()),
+    };
+    if &cached.content == content {
+        return Ok(());
+    }
+
+    let config = config::load(&mut client)?;
+    let audit_channel = match config.audit_channel {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    audit_channel.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Message edited")
+                .field(
+                    "Author",
+                    format!("<@{}> ({})", cached.author, cached.author_tag),
+                    true,
+                )
+                .field("Channel", format!("<#{}>", cached.channel), true)
+                .field("Before", content_field(&cached.content), false)
+                .field("After", content_field(content), false)
+                .field(
+                    "Link",
+                    format!(
+                        "https://discord.com/channels/{}/{}/{}",
+                        config.everyone_role.0, cached.channel, event.id.0
+                    ),
+                    false,
+                )
+        })
+    })?;
+
+    // Later edits are compared against this version
+    cached.content = content.clone();
+    audit::cache(&mut client, event.id.0, &cached)?;
+
+    Ok(())
+}
+
+// Log the last known content of a deleted message
+pub fn deleted(ctx: &Context, message: MessageId) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let cached = match audit::get(&mut client, message.0)? {
+        Some(cached) => cached,
+        None => return Ok(()),
+    };
+    audit::remove(&mut client, message.0)?;
+
+    let config = config::load(&mut client)?;
+    let audit_channel = match config.audit_channel {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+    audit_channel.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Message deleted")
+                .field(
+                    "Author",
+                    format!("<@{}> ({})", cached.author, cached.author_tag),
+                    true,
+                )
+                .field("Channel", format!("<#{}>", cached.channel), true)
+                .field("Content", content_field(&cached.content), false);
+            if !cached.attachments.is_empty() {
+                e.field(
+                    "Attachments",
+                    content_field(&cached.attachments.join("\n")),
+                    false,
+                );
+            }
+            e
+        })
+    })?;
+
+    Ok(())
+}
+
+// Check whether changes to messages in a channel should be logged
+fn is_logged(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    channel: ChannelId,
+) -> Result<bool, CommandError> {
+    // Staff channels are already private
+    let staff = [
+        Some(config.reports_channel),
+        Some(config.mentors_channel),
+        config.modlog_channel,
+        config.audit_channel,
+    ];
+    if staff.contains(&Some(channel)) || modmail::user_for(client, channel.0)?.is_some() {
+        return Ok(false);
+    }
+
+    // Team channels are only logged if the event opts in
+    let category = match channel.to_channel_cached(&ctx.cache) {
+        Some(Channel::Guild(channel)) => channel.read().category_id,
+        _ => None,
+    };
+    if category == Some(config.tables_category) {
+        return Ok(audit::log_tables(client)?);
+    }
+
+    Ok(true)
+}
+
+// Fit content in an embed field, which can't be empty
+fn content_field(text: &str) -> String {
+    if text.is_empty() {
+        return "*empty*".to_string();
+    }
+    truncate(text, 1024)
+}
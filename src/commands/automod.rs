@@ -0,0 +1,311 @@
+use chrono::prelude::*;
+use log::error;
+use regex::Regex;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+use std::sync::Arc;
+
+use crate::commands::{actions, checks::MANAGER_CHECK, moderation::capture_evidence};
+use crate::data::{
+    automod::{self, Rule, RuleAction, RuleKind},
+    cases, config, get_connection,
+    moderation::ActionKind,
+};
+
+/// The automod rules with their expressions compiled, dropped whenever the rules change
+pub struct CompiledRules;
+
+impl TypeMapKey for CompiledRules {
+    type Value = Arc<Vec<Compiled>>;
+}
+
+type Compiled = (Rule, Option<Regex>);
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Manage the rules every message is automatically checked against"]
+#[usage = "<list|add|remove>"]
+#[sub_commands(automod_list, automod_add, automod_remove)]
+pub fn automod(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~mod automod list`, `~mod automod add <kind>, <action>, [<pattern>]`, or `~mod automod remove <id>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available]
+#[checks(Manager)]
+#[description = "List all the automod rules"]
+#[num_args(0)]
+pub fn automod_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let rules = automod::all(&mut client)?;
+
+    if rules.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "There are no automod rules")?;
+        return Ok(());
+    }
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Automod rules").description(
+                rules
+                    .iter()
+                    .map(|r| match r.kind {
+                        RuleKind::Invites => format!("`#{}` **{}** → {}", r.id, r.kind, r.action),
+                        _ => format!("`#{}` **{}** `{}` → {}", r.id, r.kind, r.pattern, r.action),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("add")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Add an automod rule. The pattern is the blocked word or regular expression, or the most mentions a message may have. Invite rules take no pattern."]
+#[usage = "<word|regex|invites|mentions> <delete|warn|case|ping> [<pattern>]"]
+#[example = "word, delete, heck"]
+#[example = "mentions, ping, 5"]
+#[min_args(2)]
+pub fn automod_add(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the kind and action from args
+    let kind = match args.single::<RuleKind>() {
+        Ok(kind) => kind,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <kind>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let action = match args.single::<RuleAction>() {
+        Ok(action) => action,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <action>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Regular expressions may contain the delimiter
+    let pattern = args.rest().to_string();
+    if pattern.is_empty() && kind != RuleKind::Invites {
+        msg.channel_id
+            .say(&ctx.http, "Argument <pattern> not satisfied")?;
+        return Ok(());
+    }
+    if kind == RuleKind::Mentions && pattern.parse::<usize>().is_err() {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "Failed parsing argument <pattern>: '{}' is not a number of mentions",
+                pattern
+            ),
+        )?;
+        return Ok(());
+    }
+
+    let rule = Rule {
+        id: 0,
+        kind,
+        action,
+        pattern,
+    };
+    if let Err(why) = rule.regex() {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("Failed parsing argument <pattern>: {}", why),
+        )?;
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let rule = automod::add(&mut client, rule)?;
+    ctx.data.write().remove::<CompiledRules>();
+    msg.channel_id
+        .say(&ctx.http, format!("Added automod rule `#{}`.", rule.id))?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Remove an automod rule"]
+#[usage = "<id>"]
+#[example = "3"]
+#[num_args(1)]
+pub fn automod_remove(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = match args.single::<String>() {
+        Ok(raw) => match raw.trim_start_matches('#').parse::<u64>() {
+            Ok(id) => id,
+            Err(why) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+                return Ok(());
+            }
+        },
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if automod::remove(&mut client, id)? {
+        ctx.data.write().remove::<CompiledRules>();
+        msg.channel_id
+            .say(&ctx.http, format!("Removed automod rule `#{}`.", id))?;
+    } else {
+        msg.channel_id
+            .say(&ctx.http, format!("Automod rule `#{}` does not exist", id))?;
+    }
+
+    Ok(())
+}
+
+// Check a message against the automod rules, acting on the first one it matches
+pub fn scan(ctx: &Context, msg: &Message) -> CommandResult {
+    // Only messages from users in the server are checked
+    if msg.author.bot || msg.guild_id.is_none() {
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let rules = compiled_rules(ctx, &mut client)?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    // Mentors and managers are trusted
+    let config = config::load(&mut client)?;
+    let roles = match &msg.member {
+        Some(member) => member.roles.clone(),
+        None => Vec::new(),
+    };
+    if roles.contains(&config.mentor_role) || roles.contains(&config.manager_role) {
+        return Ok(());
+    }
+
+    let mentions = msg.mentions.len() + msg.mention_roles.len();
+    let rule = rules.iter().find(|(rule, regex)| match rule.kind {
+        RuleKind::Mentions => rule
+            .pattern
+            .parse::<usize>()
+            .map(|limit| mentions > limit)
+            .unwrap_or(false),
+        _ => regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(&msg.content)),
+    });
+    let rule = match rule {
+        Some((rule, _)) => rule,
+        None => return Ok(()),
+    };
+    let reason = format!("Automod rule #{} ({})", rule.id, rule.kind);
+
+    match rule.action {
+        RuleAction::Delete => {
+            msg.delete(ctx)?;
+            let sent = msg.author.direct_message(ctx, |m| {
+                m.content(format!(
+                    "Your message in #{} was removed because it breaks the rules of the server.",
+                    msg.channel_id.name(&ctx.cache).unwrap_or_default()
+                ))
+            });
+            if let Err(e) = sent {
+                error!("Failed to notify user of removed message: {}", e);
+            }
+        }
+        RuleAction::Warn => {
+            let bot_id = ctx.cache.read().user.id;
+            actions::apply(
+                ctx,
+                &mut client,
+                &config,
+                ActionKind::Warn,
+                msg.author.id,
+                bot_id,
+                reason,
+                None,
+                None,
+            )?;
+        }
+        RuleAction::Case => {
+            let case = cases::Case::new(
+                "automod",
+                None,
+                msg.channel_id.0,
+                reason.clone(),
+                Local::now().timestamp_millis(),
+            );
+            let mut case = cases::create(&mut client, case)?;
+
+            let header = MessageBuilder::new()
+                .push_bold(format!("[Case #{}] ", case.id))
+                .push(format!("{} matched a message", reason))
+                .build();
+            case.evidence = Some(capture_evidence(ctx, config.reports_channel, msg, header)?);
+            cases::save(&mut client, &case)?;
+        }
+        RuleAction::Ping => {
+            config.reports_channel.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .mention(&config.manager_role)
+                    .push(format!(" {} matched a message by ", reason))
+                    .mention(&msg.author)
+                    .push(format!(
+                        ": https://discord.com/channels/{}/{}/{}",
+                        msg.guild_id.map(|g| g.0).unwrap_or_default(),
+                        msg.channel_id.0,
+                        msg.id.0
+                    ))
+                    .build(),
+            )?;
+        }
+    };
+
+    Ok(())
+}
+
+// Get the rules with their expressions compiled, loading them if they changed since the last message
+fn compiled_rules(
+    ctx: &Context,
+    client: &mut redis::Connection,
+) -> Result<Arc<Vec<Compiled>>, CommandError> {
+    if let Some(rules) = ctx.data.read().get::<CompiledRules>() {
+        return Ok(Arc::clone(rules));
+    }
+
+    // Hold the lock while loading so a change in the meantime isn't overwritten
+    let mut data = ctx.data.write();
+    if let Some(rules) = data.get::<CompiledRules>() {
+        return Ok(Arc::clone(rules));
+    }
+    let rules = Arc::new(
+        automod::all(client)?
+            .into_iter()
+            .map(|rule| {
+                let regex = rule.regex().ok().flatten();
+                (rule, regex)
+            })
+            .collect::<Vec<_>>(),
+    );
+    data.insert::<CompiledRules>(Arc::clone(&rules));
+    Ok(rules)
+}
@@ -0,0 +1,195 @@
+use serenity::framework::standard::{macros::check, Args, CheckResult, CommandOptions};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::data::{
+    conduct, config, get_connection, moderation,
+    phases::{self, Phase},
+};
+
+#[check]
+#[name = "Manager"]
+pub fn manager_check(
+    ctx: &mut Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    has_role(
+        ctx,
+        msg,
+        |c| c.manager_role,
+        "You must be a manager to run this command!",
+    )
+}
+
+#[check]
+#[name = "Responder"]
+pub fn responder_check(
+    ctx: &mut Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    // Everyone paged about emergencies can acknowledge them
+    let (config, oncall) = match get_connection(&ctx.data)
+        .and_then(|mut c| Ok((config::load(&mut c)?, moderation::oncall(&mut c)?)))
+    {
+        Ok(loaded) => loaded,
+        Err(e) => return CheckResult::new_log(format!("Failed to load configuration: {}", e)),
+    };
+    if oncall.contains(&msg.author.id.0) {
+        return CheckResult::Success;
+    }
+
+    // On-call moderators are paged through direct messages, so use the event's server
+    let guild_id = GuildId(config.everyone_role.0);
+    let member = match guild_id.member(&*ctx, msg.author.id) {
+        Ok(member) => member,
+        Err(e) => return CheckResult::new_log(format!("Failed to check roles: {}", e)),
+    };
+    if member.roles.contains(&config.manager_role) || member.roles.contains(&config.mentor_role) {
+        CheckResult::Success
+    } else {
+        CheckResult::new_user("You must be a manager, mentor, or on-call to run this command!")
+    }
+}
+
+#[check]
+#[name = "Accepted"]
+pub fn accepted_check(
+    ctx: &mut Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    // There is nothing to accept until the code of conduct is configured
+    let accepted = get_connection(&ctx.data).and_then(|mut c| {
+        Ok(config::load(&mut c)?.gate_channel.is_none()
+            || conduct::has_accepted(&mut c, msg.author.id.0)?)
+    });
+
+    match accepted {
+        Ok(true) => CheckResult::Success,
+        Ok(false) => CheckResult::new_user(
+            "You must accept the code of conduct first! Read it and react to it, or run `~accept`.",
+        ),
+        Err(e) => CheckResult::new_log(format!("Failed to check code of conduct: {}", e)),
+    }
+}
+
+#[check]
+#[name = "TablesPhase"]
+pub fn tables_phase_check(
+    ctx: &mut Context,
+    _: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    in_phase(ctx, phases::TABLES)
+}
+
+#[check]
+#[name = "MentorsPhase"]
+pub fn mentors_phase_check(
+    ctx: &mut Context,
+    _: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    in_phase(ctx, phases::MENTORS)
+}
+
+#[check]
+#[name = "SubmissionsPhase"]
+pub fn submissions_phase_check(
+    ctx: &mut Context,
+    _: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    in_phase(ctx, phases::SUBMISSIONS)
+}
+
+#[check]
+#[name = "JudgingPhase"]
+pub fn judging_phase_check(
+    ctx: &mut Context,
+    _: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    in_phase(ctx, phases::JUDGING)
+}
+
+#[check]
+#[name = "Judge"]
+pub fn judge_check(
+    ctx: &mut Context,
+    msg: &Message,
+    _: &mut Args,
+    _: &CommandOptions,
+) -> CheckResult {
+    let config = match get_connection(&ctx.data).and_then(|mut c| config::load(&mut c)) {
+        Ok(config) => config,
+        Err(e) => return CheckResult::new_log(format!("Failed to load configuration: {}", e)),
+    };
+
+    let judge_role = match config.judge_role {
+        Some(role) => role,
+        None => return CheckResult::new_user("Judging is not configured!"),
+    };
+
+    // Judges score through direct messages, so use the event's server
+    let guild_id = GuildId(config.everyone_role.0);
+    match msg.author.has_role(&ctx.http, guild_id, judge_role) {
+        Ok(true) => CheckResult::Success,
+        Ok(false) => CheckResult::new_user("You must be a judge to run this command!"),
+        Err(e) => CheckResult::new_log(format!("Failed to check roles: {}", e)),
+    }
+}
+
+// Check that the event is in one of the allowed phases
+fn in_phase(ctx: &mut Context, allowed: &[Phase]) -> CheckResult {
+    let mut client = match get_connection(&ctx.data) {
+        Ok(client) => client,
+        Err(e) => return CheckResult::new_log(format!("Failed to connect to redis: {}", e)),
+    };
+
+    match phases::is_in(&mut client, allowed) {
+        Ok(true) => CheckResult::Success,
+        Ok(false) => CheckResult::new_user(format!(
+            "This command is only available during {}!",
+            allowed
+                .iter()
+                .map(Phase::to_string)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        )),
+        Err(e) => CheckResult::new_log(format!("Failed to check event phase: {}", e)),
+    }
+}
+
+// Check that the author has the role selected from the configuration
+fn has_role(
+    ctx: &mut Context,
+    msg: &Message,
+    role: impl Fn(&config::Config) -> RoleId,
+    reason: &str,
+) -> CheckResult {
+    let guild_id = match msg.guild_id {
+        Some(id) => id,
+        None => return CheckResult::new_user("This command can only be run in a server"),
+    };
+
+    let config = match get_connection(&ctx.data).and_then(|mut c| config::load(&mut c)) {
+        Ok(config) => config,
+        Err(e) => return CheckResult::new_log(format!("Failed to load configuration: {}", e)),
+    };
+
+    match msg.author.has_role(&ctx.http, guild_id, role(&config)) {
+        Ok(true) => CheckResult::Success,
+        Ok(false) => CheckResult::new_user(reason),
+        Err(e) => CheckResult::new_log(format!("Failed to check roles: {}", e)),
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::prelude::*;
+use log::error;
+use redis::Commands;
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::onboarding;
+use crate::data::{conduct, config, get_connection, moderation, roster};
+
+// Reaction members use to accept the code of conduct
+const ACCEPT_EMOJI: &str = "✅";
+
+// Longest message Discord allows
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+#[command]
+#[help_available]
+#[description = "Accept the code of conduct. This is required before joining a table or requesting a mentor."]
+#[num_args(0)]
+pub fn accept(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
+    if conduct::message(&mut client)?.is_none() {
+        msg.channel_id
+            .say(&ctx.http, "There is no code of conduct to accept yet")?;
+        return Ok(());
+    }
+
+    if !record(ctx, &mut client, &config, msg.author.id)? {
+        msg.channel_id
+            .say(&ctx.http, "You have already accepted the code of conduct")?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Manage the code of conduct members must accept"]
+#[usage = "<post|export>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(coc_post, coc_export)]
+pub fn coc(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin coc post <text>` (or with the text attached) or `~admin coc export`",
+    )?;
+
+    Ok(())
+}
+
+#[command("post")]
+#[help_available(false)]
+#[description = "Post the code of conduct in the gate channel. Once posted, members must accept it to participate."]
+#[usage = "[<text>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(0)]
+pub fn coc_post(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    // Get the text from an attachment or the rest of the message
+    let text = match msg.attachments.first() {
+        Some(attachment) => String::from_utf8_lossy(&attachment.download()?).to_string(),
+        None => args.rest().to_string(),
+    };
+    if text.trim().is_empty() {
+        msg.channel_id.say(
+            &ctx.http,
+            "Please include the code of conduct in the message or attach it",
+        )?;
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let gate_channel = match config::load(&mut client)?.gate_channel {
+        Some(channel) => channel,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                "The code of conduct is not configured, set `gate_channel` with `~admin config set` first",
+            )?;
+            return Ok(());
+        }
+    };
+
+    // Split long documents on line breaks to fit in messages
+    let mut chunks = vec![String::new()];
+    for line in text.lines() {
+        let current = chunks.last_mut().unwrap();
+        if !current.is_empty() && current.len() + line.len() + 1 > MAX_MESSAGE_LENGTH {
+            chunks.push(String::new());
+        }
+        let current = chunks.last_mut().unwrap();
+        current.push_str(
+            &line
+                .chars()
+                .take(MAX_MESSAGE_LENGTH - 1)
+                .collect::<String>(),
+        );
+        current.push('\n');
+    }
+    for chunk in &chunks {
+        gate_channel.say(&ctx.http, chunk)?;
+    }
+
+    let prompt = gate_channel.say(
+        &ctx.http,
+        format!(
+            "React with {} or run `~accept` to accept the code of conduct.",
+            ACCEPT_EMOJI
+        ),
+    )?;
+    prompt.react(&*ctx, ACCEPT_EMOJI)?;
+    conduct::set_message(&mut client, prompt.id.0)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Posted the code of conduct in ")
+            .channel(gate_channel)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("export")]
+#[help_available(false)]
+#[description = "Export when every member accepted the code of conduct as a CSV. The list is sent through a direct message."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn coc_export(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let mut acceptances = conduct::all(&mut client)?.into_iter().collect::<Vec<_>>();
+    acceptances.sort_by_key(|(_, at)| *at);
+
+    let mut csv = String::from("user_id,username,accepted_at\n");
+    for (user, at) in &acceptances {
+        let name = UserId(*user)
+            .to_user_cached(&ctx.cache)
+            .map(|u| u.read().tag())
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},\"{}\",{}\n",
+            user,
+            name.replace('"', "\"\""),
+            Utc.timestamp_millis_opt(*at)
+                .single()
+                .map(|at| at.to_rfc3339())
+                .unwrap_or_default()
+        ));
+    }
+
+    // Send it privately as it contains member information
+    let filename = format!("coc-acceptances-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    msg.author.create_dm_channel(&ctx.http)?.send_files(
+        &ctx.http,
+        vec![(csv.as_bytes(), filename.as_str())],
+        |m| {
+            m.content(format!(
+                "{} members have accepted the code of conduct",
+                acceptances.len()
+            ))
+        },
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Sent the acceptance list to ")
+            .mention(&msg.author)
+            .push(" through a direct message.")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+// Accept the code of conduct for users reacting to it
+pub fn accept_reaction(ctx: &Context, reaction: &Reaction) -> CommandResult {
+    if reaction.emoji != ReactionType::Unicode(ACCEPT_EMOJI.to_string()) {
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    if conduct::message(&mut client)? != Some(reaction.message_id.0) {
+        return Ok(());
+    }
+
+    let config = config::load(&mut client)?;
+    record(ctx, &mut client, &config, reaction.user_id)?;
+
+    Ok(())
+}
+
+// Give a member the Teamless role once they have accepted the code of conduct,
+// otherwise point them to it, and start onboarding them
+pub fn let_in(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: UserId,
+) -> CommandResult {
+    if let Err(e) = onboarding::start(ctx, client, user) {
+        error!("Failed to start onboarding: {:?}", e);
+    }
+
+    // Without a gate channel there is no code of conduct to accept
+    let gate_channel = match config.gate_channel {
+        Some(channel) if !conduct::has_accepted(client, user.0)? => channel,
+        _ => return give_teamless(ctx, client, config, user),
+    };
+
+    let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push("Before participating, please read the code of conduct in ")
+                .channel(gate_channel)
+                .push(format!(
+                    " and accept it by reacting with {} or running `~accept`.",
+                    ACCEPT_EMOJI
+                ))
+                .build(),
+        )
+    });
+    if let Err(e) = sent {
+        error!("Failed to send code of conduct prompt: {}", e);
+    }
+
+    Ok(())
+}
+
+// Record that a member accepted the code of conduct and let them in if they are
+// otherwise allowed, returning whether they hadn't already accepted it
+fn record(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: UserId,
+) -> Result<bool, serenity::framework::standard::CommandError> {
+    if !conduct::accept(client, user.0, Utc::now().timestamp_millis())? {
+        return Ok(false);
+    }
+
+    // Members still being verified or quarantined are let in once that's done
+    let waiting =
+        roster::is_pending(client, user.0)? || moderation::is_quarantined(client, user.0)?;
+    if !waiting {
+        give_teamless(ctx, client, config, user)?;
+    }
+
+    let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            "Thanks for accepting the code of conduct! You can now join a table with `~join` and request help with `~mentor request`.",
+        )
+    });
+    if let Err(e) = sent {
+        error!("Failed to confirm code of conduct acceptance: {}", e);
+    }
+
+    Ok(true)
+}
+
+// Give a member the Teamless role unless they have already joined a table
+fn give_teamless(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: UserId,
+) -> CommandResult {
+    let table: Option<String> = client.hget("tables", user.0)?;
+    if table.is_none() {
+        ctx.http
+            .add_member_role(config.everyone_role.0, user.0, config.teamless_role.0)?;
+    }
+
+    Ok(())
+}
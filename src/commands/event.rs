@@ -0,0 +1,110 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::announcements;
+use crate::data::{event, get_connection};
+use crate::util::{format_time, parse_time};
+
+#[command]
+#[help_available(false)]
+#[description = "Show the event timezone and when the event ends"]
+#[usage = "[<timezone|end>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+#[sub_commands(event_timezone, event_end)]
+pub fn event(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let end = event::end(&mut client)?;
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Event")
+                .field("Timezone", timezone.name(), true)
+                .field(
+                    "Ends",
+                    match end {
+                        Some(end) => format_time(end, timezone),
+                        None => "*not set*".to_string(),
+                    },
+                    true,
+                )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("timezone")]
+#[help_available(false)]
+#[description = "Set the timezone times are entered and shown in"]
+#[usage = "<timezone>"]
+#[example = "America/Los_Angeles"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn event_timezone(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let timezone = match args.rest().trim().parse::<Tz>() {
+        Ok(timezone) => timezone,
+        Err(_) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <timezone>: '{}' must be a timezone like `America/Los_Angeles`",
+                    args.rest()
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    event::set_timezone(&mut client, timezone)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Times are now entered and shown in {}.", timezone.name()),
+    )?;
+
+    Ok(())
+}
+
+#[command("end")]
+#[help_available(false)]
+#[description = "Set when the event ends, which countdowns are relative to. The time is either relative or a date and time in the event timezone."]
+#[usage = "<time>"]
+#[example = "2020-10-25 12:00"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn event_end(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let at = match parse_time(args.rest(), Utc::now().timestamp_millis(), timezone) {
+        Some(at) => at,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <time>: '{}' must be like `in 2h` or `2020-10-25 12:00`",
+                    args.rest()
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    event::set_end(&mut client, at)?;
+    announcements::reschedule_countdowns(&mut client)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("The event now ends {}.", format_time(at, timezone)),
+    )?;
+
+    Ok(())
+}
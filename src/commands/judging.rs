@@ -0,0 +1,734 @@
+use chrono::prelude::*;
+use log::error;
+use redis::Commands;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+use std::collections::HashMap;
+
+use crate::data::{
+    config, get_connection,
+    judging::{self, Criterion, Score, Standing},
+    submissions::{self, Submission},
+};
+use crate::util::{parse_user_id, truncate};
+
+// Most tables or tracks that fit in an embed
+const MAX_FIELDS: usize = 25;
+
+// Tables shown for each track in the results
+const MAX_STANDINGS: usize = 5;
+
+// Track every submission is ranked in
+const OVERALL: &str = "Overall";
+
+#[command("assignments")]
+#[help_available]
+#[description = "Show the rubric and the tables you've been assigned to judge"]
+#[only_in("dms")]
+#[num_args(0)]
+pub fn judge_assignments(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let tables = judging::assignments(&mut client, msg.author.id.0)?;
+    if tables.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "You haven't been assigned any tables yet")?;
+        return Ok(());
+    }
+
+    let rubric = judging::rubric(&mut client)?;
+    let all = submissions::all(&mut client)?;
+    let scored = scored_by(&mut client, msg.author.id.0)?;
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Your assignments").description(format!(
+                "Score each table with `~judge score <table>, {}`.\n\n{}",
+                rubric
+                    .iter()
+                    .map(|c| format!("<{}>", c.name.to_lowercase()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                describe_rubric(&rubric)
+            ));
+            for table in tables.iter().take(MAX_FIELDS) {
+                e.field(
+                    format!(
+                        "{}{}",
+                        table,
+                        if scored.contains_key(table) {
+                            " (scored)"
+                        } else {
+                            ""
+                        }
+                    ),
+                    match all.get(table) {
+                        Some(s) => format!("[{}]({})", s.title, s.link),
+                        None => "*no submission*".to_string(),
+                    },
+                    false,
+                );
+            }
+            e
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("score")]
+#[help_available]
+#[description = "Score a table you've been assigned on every criterion of the rubric, in order. Scoring it again replaces your previous scores."]
+#[usage = "<table>, <score>..."]
+#[example = "5, 8, 7, 9"]
+#[only_in("dms")]
+#[min_args(2)]
+pub fn judge_score(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let rubric = judging::rubric(&mut client)?;
+    if rubric.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "The rubric hasn't been set up yet")?;
+        return Ok(());
+    }
+    let assigned = judging::assignments(&mut client, msg.author.id.0)?;
+
+    // Get the table from args
+    let table = table_name(&args.single::<String>()?);
+    if !assigned.contains(&table)
+        || judging::conflicts(&mut client, msg.author.id.0)?.contains(&table)
+    {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "You haven't been assigned {}, use `~judge assignments` to see your tables",
+                table
+            ),
+        )?;
+        return Ok(());
+    }
+
+    // Get a score for every criterion from args
+    if args.remaining() != rubric.len() {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "Expected {} scores, one for each of {}",
+                rubric.len(),
+                rubric
+                    .iter()
+                    .map(|c| c.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )?;
+        return Ok(());
+    }
+    let mut scores = HashMap::new();
+    for criterion in &rubric {
+        match args.single::<u32>() {
+            Ok(score) if score <= criterion.scale => {
+                scores.insert(criterion.name.clone(), score);
+            }
+            Ok(_) | Err(ArgError::Parse(_)) => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <{}>: must be a whole number from 0 to {}",
+                        criterion.name.to_lowercase(),
+                        criterion.scale
+                    ),
+                )?;
+                return Ok(());
+            }
+            Err(e) => return Err(CommandError(e.to_string())),
+        }
+    }
+
+    let score = Score {
+        judge: msg.author.id.0,
+        table: table.clone(),
+        scores,
+        updated_at: Utc::now().timestamp_millis(),
+    };
+    judging::save_score(&mut client, &score)?;
+
+    let scored = scored_by(&mut client, msg.author.id.0)?;
+    let done = assigned.iter().filter(|t| scored.contains_key(*t)).count();
+    msg.channel_id.say(
+        &ctx.http,
+        format!(
+            "Saved your scores for {} ({:.1} out of 100). You've scored {} of your {} tables.",
+            table,
+            score.total(&rubric),
+            done,
+            assigned.len()
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command("rubric")]
+#[help_available(false)]
+#[description = "Show the criteria judges score projects on"]
+#[usage = "[<add|remove>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+#[sub_commands(rubric_add, rubric_remove)]
+pub fn rubric_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let rubric = judging::rubric(&mut client)?;
+    if rubric.is_empty() {
+        msg.channel_id.say(
+            &ctx.http,
+            "The rubric is empty, add criteria with `~admin rubric add <name>, <weight>, <scale>`",
+        )?;
+        return Ok(());
+    }
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| e.title("Rubric").description(describe_rubric(&rubric)))
+    })?;
+
+    Ok(())
+}
+
+#[command("add")]
+#[help_available(false)]
+#[description = "Add a criterion to the rubric, or change the weight and scale of an existing one. Judges score it from 0 to the scale."]
+#[usage = "<name>, <weight>, <scale>"]
+#[example = "Technical Difficulty, 2, 10"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(3)]
+pub fn rubric_add(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the criterion from args
+    let name = args.single::<String>()?.trim().to_string();
+    let weight = match args.single::<f64>() {
+        Ok(weight) if weight > 0.0 => weight,
+        Ok(_) | Err(ArgError::Parse(_)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Failed parsing argument <weight>: must be a positive number",
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let scale = match args.single::<u32>() {
+        Ok(scale) if scale > 0 => scale,
+        Ok(_) | Err(ArgError::Parse(_)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Failed parsing argument <scale>: must be a positive whole number",
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let mut rubric = judging::rubric(&mut client)?;
+    let criterion = Criterion {
+        name: name.clone(),
+        weight,
+        scale,
+    };
+    let updated = match rubric
+        .iter_mut()
+        .find(|c| c.name.eq_ignore_ascii_case(&name))
+    {
+        Some(existing) => {
+            *existing = criterion;
+            true
+        }
+        None => {
+            rubric.push(criterion);
+            false
+        }
+    };
+    judging::set_rubric(&mut client, &rubric)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!(
+            "{} **{}** with a weight of {} scored out of {}.",
+            if updated { "Updated" } else { "Added" },
+            name,
+            weight,
+            scale
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[help_available(false)]
+#[description = "Remove a criterion from the rubric. Scores already given for it are ignored."]
+#[usage = "<name>"]
+#[example = "Technical Difficulty"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn rubric_remove(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let name = args.rest().trim();
+
+    let mut client = get_connection(&ctx.data)?;
+    let mut rubric = judging::rubric(&mut client)?;
+    let before = rubric.len();
+    rubric.retain(|c| !c.name.eq_ignore_ascii_case(name));
+    if rubric.len() == before {
+        msg.channel_id
+            .say(&ctx.http, format!("**{}** is not on the rubric", name))?;
+        return Ok(());
+    }
+    judging::set_rubric(&mut client, &rubric)?;
+
+    msg.channel_id
+        .say(&ctx.http, format!("Removed **{}** from the rubric.", name))?;
+
+    Ok(())
+}
+
+#[command("judging")]
+#[help_available(false)]
+#[description = "Show the tracks and how many scores judges have entered"]
+#[usage = "[<tracks|conflict|assign|results|export>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+#[sub_commands(
+    judging_tracks,
+    judging_conflict,
+    judging_assign,
+    judging_results,
+    judging_export
+)]
+pub fn judging_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let tracks = judging::tracks(&mut client)?;
+    let assignments = judging::all_assignments(&mut client)?;
+    let scores = judging::scores(&mut client)?;
+
+    let mut progress = assignments
+        .iter()
+        .map(|(judge, tables)| {
+            let done = tables
+                .iter()
+                .filter(|t| scores.iter().any(|s| s.judge == *judge && s.table == **t))
+                .count();
+            (done, tables.len(), *judge)
+        })
+        .collect::<Vec<_>>();
+    progress.sort();
+    let (done, total) = progress
+        .iter()
+        .fold((0, 0), |(d, t), (done, total, _)| (d + done, t + total));
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Judging")
+                .field(
+                    "Tracks",
+                    if tracks.is_empty() {
+                        "*none*".to_string()
+                    } else {
+                        tracks.join(", ")
+                    },
+                    false,
+                )
+                .field(
+                    format!("Scores entered ({} of {})", done, total),
+                    if progress.is_empty() {
+                        "*no tables have been assigned*".to_string()
+                    } else {
+                        truncate(
+                            &progress
+                                .iter()
+                                .map(|(done, total, judge)| {
+                                    format!("<@{}>: {} of {}", judge, done, total)
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                            1024,
+                        )
+                    },
+                    false,
+                )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("tracks")]
+#[help_available(false)]
+#[description = "Replace the tracks projects can enter when they submit. Every project is also ranked overall."]
+#[usage = "<track>..."]
+#[example = "Best Hardware Hack, Best Beginner Hack"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn judging_tracks(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut tracks = Vec::new();
+    for raw in args.iter::<String>() {
+        let track = raw?.trim().to_string();
+        if !track.is_empty() && !tracks.contains(&track) {
+            tracks.push(track);
+        }
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    judging::set_tracks(&mut client, &tracks)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Projects can now enter {}.", tracks.join(", ")),
+    )?;
+
+    Ok(())
+}
+
+#[command("conflict")]
+#[help_available(false)]
+#[description = "Prevent a judge from being assigned a table. Judges are never assigned their own table."]
+#[usage = "<judge>, <table>"]
+#[example = "@Judge, 5"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(2)]
+pub fn judging_conflict(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the judge and table from args
+    let judge = match args.single::<String>() {
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => id,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <judge>: '{}'", raw),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <judge> not satisfied")?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let table = table_name(args.rest());
+
+    let mut client = get_connection(&ctx.data)?;
+    judging::add_conflict(&mut client, judge.0, &table)?;
+
+    let mut reply = MessageBuilder::new();
+    reply
+        .mention(&judge)
+        .push(" won't be assigned ")
+        .push_mono(&table)
+        .push(".");
+    if judging::assignments(&mut client, judge.0)?.contains(&table) {
+        reply.push(" Any scores they gave it are ignored, and since they are currently assigned it, run `~admin judging assign` again.");
+    }
+    msg.channel_id.say(&ctx.http, reply.build())?;
+
+    Ok(())
+}
+
+#[command("assign")]
+#[help_available(false)]
+#[description = "Assign every submitted table to judges, spreading tables evenly and avoiding conflicts of interest. This replaces the previous assignments but keeps any scores already entered."]
+#[usage = "<judges per table>"]
+#[example = "3"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn judging_assign(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let per_table = match args.single::<usize>() {
+        Ok(count) if count > 0 => count,
+        Ok(_) | Err(ArgError::Parse(_)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Failed parsing argument <judges per table>: must be a positive whole number",
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    let judge_role = match config.judge_role {
+        Some(role) => role,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Judging is not configured, set `judge_role` with `~admin config set` first",
+            )?;
+            return Ok(());
+        }
+    };
+
+    // Everyone with the judge role can be assigned. The cache only has the members who have
+    // been seen recently, so page through every member instead.
+    let mut judges = Vec::new();
+    for member in GuildId(config.everyone_role.0).members_iter(&ctx.http) {
+        let member = member?;
+        if member.roles.contains(&judge_role) {
+            judges.push(member.user.read().id.0);
+        }
+    }
+    judges.sort();
+    if judges.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Nobody has the judge role yet")?;
+        return Ok(());
+    }
+
+    let mut tables = submissions::all(&mut client)?
+        .into_keys()
+        .collect::<Vec<_>>();
+    submissions::sort_tables(&mut tables);
+    if tables.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "No tables have submitted a project yet")?;
+        return Ok(());
+    }
+
+    // Judges conflict with their own table and any the organizers added
+    let members: HashMap<u64, String> = client.hgetall("tables")?;
+    let mut conflicts = HashMap::new();
+    for judge in &judges {
+        let mut declared = judging::conflicts(&mut client, *judge)?;
+        if let Some(table) = members.get(judge) {
+            declared.insert(table.clone());
+        }
+        conflicts.insert(*judge, declared);
+    }
+
+    // Give each table to the least loaded judges without a conflict
+    let mut assignments: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut short = Vec::new();
+    for table in &tables {
+        let mut eligible = judges
+            .iter()
+            .filter(|j| !conflicts[*j].contains(table))
+            .map(|j| (assignments.get(j).map_or(0, Vec::len), *j))
+            .collect::<Vec<_>>();
+        eligible.sort();
+        if eligible.len() < per_table {
+            short.push(table.as_str());
+        }
+        for (_, judge) in eligible.into_iter().take(per_table) {
+            assignments.entry(judge).or_default().push(table.clone());
+        }
+    }
+    judging::set_assignments(&mut client, &assignments)?;
+
+    // Let every judge know what to do next
+    for (judge, assigned) in &assignments {
+        let sent = UserId(*judge).create_dm_channel(&ctx.http).and_then(|c| {
+            c.say(
+                &ctx.http,
+                format!(
+                    "You've been assigned {} tables to judge. Use `~judge assignments` here to see them and the rubric, then `~judge score` to score each one.",
+                    assigned.len()
+                ),
+            )
+        });
+        if let Err(e) = sent {
+            error!(
+                "Failed to notify judge {} of their assignments: {}",
+                judge, e
+            );
+        }
+    }
+
+    let mut reply = format!(
+        "Assigned {} tables to {} judges, with up to {} judges each.",
+        tables.len(),
+        assignments.len(),
+        per_table
+    );
+    if !short.is_empty() {
+        reply.push_str(&format!(
+            " There weren't enough judges without a conflict for {}.",
+            short.join(", ")
+        ));
+    }
+    msg.channel_id.say(&ctx.http, reply)?;
+
+    Ok(())
+}
+
+#[command("results")]
+#[help_available(false)]
+#[description = "Show the top tables in each track. Each judge's scores are normalized before they are averaged so harsh and generous judges count the same."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn judging_results(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let rankings = rankings(&mut client)?;
+    if rankings.iter().all(|(_, standings)| standings.is_empty()) {
+        msg.channel_id
+            .say(&ctx.http, "No scores have been entered yet")?;
+        return Ok(());
+    }
+    let all = submissions::all(&mut client)?;
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Judging results");
+            for (track, standings) in rankings.iter().take(MAX_FIELDS) {
+                e.field(
+                    track,
+                    if standings.is_empty() {
+                        "*no scores*".to_string()
+                    } else {
+                        standings
+                            .iter()
+                            .take(MAX_STANDINGS)
+                            .enumerate()
+                            .map(|(i, s)| {
+                                format!(
+                                    "{}. **{}** {} ({:+.2}, {} judges)",
+                                    i + 1,
+                                    s.table,
+                                    all.get(&s.table).map_or("", |s| s.title.as_str()),
+                                    s.normalized,
+                                    s.judges
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    },
+                    false,
+                );
+            }
+            e.footer(|f| f.text("Use `~admin judging export` for the full rankings"))
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("export")]
+#[help_available(false)]
+#[description = "Export the full rankings in every track as a CSV. The rankings are sent through a direct message."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn judging_export(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let rankings = rankings(&mut client)?;
+    let all = submissions::all(&mut client)?;
+
+    let mut csv = String::from("track,rank,table,title,link,judges,average,normalized\n");
+    for (track, standings) in &rankings {
+        for (i, standing) in standings.iter().enumerate() {
+            let (title, link) = all
+                .get(&standing.table)
+                .map_or(("", ""), |s| (s.title.as_str(), s.link.as_str()));
+            csv.push_str(&format!(
+                "\"{}\",{},{},\"{}\",\"{}\",{},{:.2},{:.4}\n",
+                track.replace('"', "\"\""),
+                i + 1,
+                standing.table,
+                title.replace('"', "\"\""),
+                link.replace('"', "\"\""),
+                standing.judges,
+                standing.average,
+                standing.normalized
+            ));
+        }
+    }
+
+    // Send it privately until the winners are announced
+    let filename = format!("judging-{}.csv", Utc::now().format("%Y%m%d-%H%M%S"));
+    msg.author.create_dm_channel(&ctx.http)?.send_files(
+        &ctx.http,
+        vec![(csv.as_bytes(), filename.as_str())],
+        |m| m.content("Rankings in every track"),
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Sent the rankings to ")
+            .mention(&msg.author)
+            .push(" through a direct message.")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+// Rank the tables overall and in each track
+fn rankings(client: &mut redis::Connection) -> Result<Vec<(String, Vec<Standing>)>, CommandError> {
+    let rubric = judging::rubric(client)?;
+    let all = submissions::all(client)?;
+
+    // Scores from conflicted or reassigned judges don't count
+    let assignments = judging::all_assignments(client)?;
+    let mut conflicts = HashMap::new();
+    for judge in assignments.keys() {
+        conflicts.insert(*judge, judging::conflicts(client, *judge)?);
+    }
+    let scores = judging::counted(judging::scores(client)?, &assignments, &conflicts);
+
+    // Every judge is normalized across all their scores, then each track keeps its tables
+    let overall = judging::rank(&rubric, &scores);
+    let mut rankings = Vec::new();
+    for track in judging::tracks(client)? {
+        let entered = |s: &Submission| s.tracks.contains(&track);
+        let standings = overall
+            .iter()
+            .filter(|s| all.get(&s.table).is_some_and(entered))
+            .cloned()
+            .collect::<Vec<_>>();
+        rankings.push((track.clone(), standings));
+    }
+    rankings.insert(0, (OVERALL.to_string(), overall));
+
+    Ok(rankings)
+}
+
+// Get the scores a judge has entered by table
+fn scored_by(
+    client: &mut redis::Connection,
+    judge: u64,
+) -> Result<HashMap<String, Score>, CommandError> {
+    Ok(judging::scores(client)?
+        .into_iter()
+        .filter(|s| s.judge == judge)
+        .map(|s| (s.table.clone(), s))
+        .collect())
+}
+
+// Describe every criterion on the rubric
+fn describe_rubric(rubric: &[Criterion]) -> String {
+    if rubric.is_empty() {
+        return "*The rubric hasn't been set up yet*".to_string();
+    }
+
+    rubric
+        .iter()
+        .map(|c| format!("**{}**: 0 to {}, weight {}", c.name, c.scale, c.weight))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Get a table's name from its number or full name
+fn table_name(raw: &str) -> String {
+    let raw = raw.trim();
+    match raw.parse::<i64>() {
+        Ok(number) => format!("Table {}", number),
+        Err(_) => raw.to_string(),
+    }
+}
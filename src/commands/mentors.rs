@@ -6,8 +6,9 @@ use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 
-use crate::data::{add_help_request, get_connection, get_help_request};
-use crate::util::{MENTORS_CHANNEL_ID, MENTOR_ROLE_ID};
+use crate::commands::checks::ACCEPTED_CHECK;
+use crate::data::{add_help_request, config, event, get_connection, get_help_request};
+use crate::util::format_time;
 
 #[command]
 #[help_available]
@@ -16,6 +17,7 @@ use crate::util::{MENTORS_CHANNEL_ID, MENTOR_ROLE_ID};
 #[example = "some description, https://github.com/test/test"]
 #[min_args(1)]
 #[max_args(2)]
+#[checks(Accepted)]
 pub fn request(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     // Get description from args
     let description = match args.single::<String>() {
@@ -49,8 +51,9 @@ pub fn request(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResul
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
-    // Retrieve redis connection
+    // Retrieve redis connection and configuration
     let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
 
     // Retrieve team from database
     let team = match client.hget::<&str, u64, Option<String>>("tables", msg.author.id.0)? {
@@ -59,7 +62,7 @@ pub fn request(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResul
     };
 
     // Get the current time
-    let current_time: DateTime<Local> = Local::now();
+    let current_time = Utc::now();
 
     // Set values
     add_help_request(
@@ -79,14 +82,8 @@ pub fn request(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResul
             .push("."),
     )?;
 
-    // Retrieve channel
-    let channel = ctx
-        .http
-        .get_channel(MENTORS_CHANNEL_ID.clone())
-        .expect("Invalid channel ID");
-
     // Send notification to mentors
-    channel.id().say(
+    config.mentors_channel.say(
         &ctx.http,
         MessageBuilder::new()
             .push("New help request from ")
@@ -107,10 +104,14 @@ pub fn request(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResul
 #[description = "List all help requests"]
 #[num_args(0)]
 pub fn list(ctx: &mut Context, msg: &Message, _: Args) -> CommandResult {
+    // Retrieve redis connection and configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
     // Check if current user is a mentor
     if !msg
         .author
-        .has_role(&ctx.http, msg.guild_id.unwrap(), *MENTOR_ROLE_ID)?
+        .has_role(&ctx.http, msg.guild_id.unwrap(), config.mentor_role)?
     {
         msg.channel_id.say(
             &ctx.http,
@@ -122,14 +123,12 @@ pub fn list(ctx: &mut Context, msg: &Message, _: Args) -> CommandResult {
         return Ok(());
     }
 
-    // Retrieve redis connection
-    let mut client = get_connection(&ctx.data)?;
-
     // Get all requests
     let requests: redis::Iter<String> = client.scan_match("help_request:*")?;
 
     // Get a new client
     let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
 
     // Send table of help requests
     msg.channel_id.send_message(&ctx.http, |m| {
@@ -151,15 +150,13 @@ pub fn list(ctx: &mut Context, msg: &Message, _: Args) -> CommandResult {
                 if ts == 0 {
                     continue;
                 }
+                let ts = format_time(ts, timezone);
 
                 e.field(
                     key.get(13..21).unwrap(),
                     format!(
                         "**Timestamp**: {}\n**Description**: {}\n**Link**: {}\n**For**: {}",
-                        Local.timestamp(ts / 1000, 0).to_string(),
-                        desc,
-                        link,
-                        table
+                        ts, desc, link, table
                     ),
                     true,
                 );
@@ -179,10 +176,14 @@ pub fn list(ctx: &mut Context, msg: &Message, _: Args) -> CommandResult {
 #[example = "abcd1234"]
 #[num_args(1)]
 pub fn complete(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Retrieve redis connection and configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
     // Check if current user is a mentor
     if !msg
         .author
-        .has_role(&ctx.http, msg.guild_id.unwrap(), *MENTOR_ROLE_ID)?
+        .has_role(&ctx.http, msg.guild_id.unwrap(), config.mentor_role)?
     {
         msg.channel_id.say(
             &ctx.http,
@@ -210,8 +211,7 @@ pub fn complete(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResu
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
-    // Retrieve connection and delete
-    let mut client = get_connection(&ctx.data)?;
+    // Delete the request
     client.del(format!("help_request:{}", &id))?;
 
     // Send confirmation
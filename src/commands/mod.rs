@@ -1,4 +1,23 @@
+pub mod actions;
 pub mod admin;
+pub mod agenda;
+pub mod announcements;
+pub mod attendance;
+pub mod audit;
+pub mod automod;
+pub mod checks;
+pub mod conduct;
+pub mod event;
+pub mod judging;
 pub mod mentors;
 pub mod moderation;
+pub mod moderators;
+pub mod modmail;
+pub mod onboarding;
+pub mod phases;
+pub mod raid;
+pub mod ratelimit;
+pub mod submissions;
 pub mod tables;
+pub mod verify;
+pub mod workshops;
@@ -1,58 +1,302 @@
+use chrono::prelude::*;
+use log::error;
 use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 
-use crate::util::{MANAGER_ROLE_ID, MENTOR_ROLE_ID, REPORTS_CHANNEL_ID};
+use crate::data::{cases, config, get_connection, moderation};
+use crate::scheduler::{self, Job};
+use crate::util::parse_message_link;
+
+/// The reaction used to acknowledge an emergency
+pub const ACKNOWLEDGE_EMOJI: &str = "✅";
+
+// How long to wait before paging again in milliseconds
+const REPAGE_INTERVAL: i64 = 2 * 60 * 1000;
+
+// How many pages before the on-call moderators are notified directly
+const ESCALATE_AFTER: u32 = 3;
+
+// Discord's upload limit for bots without boosts
+const MAX_ATTACHMENT_SIZE: u64 = 8 * 1024 * 1024;
 
 #[command]
 #[help_available]
-#[description = "Send a report of non-immediate importance"]
-#[usage = "<message>"]
+#[description = "Send a report of non-immediate importance. A specific message can be reported by linking to it or by replying to it. This can also be sent to the bot in a direct message."]
+#[usage = "[<message link>] <message>"]
 #[example = "Help! <username> is being a prick"]
-#[num_args(1)]
-pub fn report(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
-    // Get message from args
-    let message = match args.single::<String>() {
-        Ok(msg) => msg,
-        Err(ArgError::Eos) => {
-            msg.channel_id
-                .say(&ctx.http, "Argument <message> not satisfied")?;
-            return Ok(());
-        }
-        Err(ArgError::Parse(why)) => {
+#[example = "https://discord.com/channels/1/2/3, this message is offensive"]
+#[min_args(0)]
+pub fn report(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    file_report(ctx, msg, args, false)
+}
+
+#[command]
+#[help_available]
+#[description = "Send a report without revealing your identity to the moderators. This must be sent to the bot in a direct message."]
+#[usage = "[<message link>] <message>"]
+#[example = "someone in #general keeps harassing me"]
+#[aliases("anon")]
+#[only_in("dms")]
+#[min_args(0)]
+pub fn anonymous(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    file_report(ctx, msg, args, true)
+}
+
+#[command]
+#[help_available]
+#[description = "Add more information to a report you made. This must be sent to the bot in a direct message."]
+#[usage = "<case id> <message>"]
+#[example = "12, it happened again in #general"]
+#[only_in("dms")]
+#[min_args(2)]
+pub fn followup(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the case id from args, allowing it to be prefixed with '#'
+    let id = match args
+        .single::<String>()
+        .map(|raw| raw.trim_start_matches('#').parse::<u64>())
+    {
+        Ok(Ok(id)) => id,
+        Ok(Err(why)) => {
             msg.channel_id.say(
                 &ctx.http,
-                format!("Failed parsing argument <message>: {}", why),
+                format!("Failed parsing argument <case id>: {}", why),
             )?;
             return Ok(());
         }
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
-    // Retrieve channel
-    let channel = ctx
-        .http
-        .get_channel(REPORTS_CHANNEL_ID.clone())
-        .expect("Invalid channel ID");
+    // Get message from the remaining args, it may contain the delimiter
+    let message = args.rest().to_string();
 
-    // Send message to reports channel
-    channel.id().say(
+    // Only the reporter can follow up on their case
+    let mut client = get_connection(&ctx.data)?;
+    let mut case = match cases::get(&mut client, id)? {
+        Some(case) if case.reporter == Some(msg.author.id.0) => case,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, format!("You have no case `#{}`", id))?;
+            return Ok(());
+        }
+    };
+
+    case.notes.push(cases::Note {
+        author: msg.author.id.0,
+        from_reporter: true,
+        content: message.clone(),
+        at: Local::now().timestamp_millis(),
+    });
+    case.updated_at = Local::now().timestamp_millis();
+    cases::save(&mut client, &case)?;
+
+    // Send the follow up to the reports channel
+    let config = config::load(&mut client)?;
+    config.reports_channel.say(
         &ctx.http,
         MessageBuilder::new()
-            .mention(&msg.author)
-            .push(" reported message '")
+            .push_bold(format!("[Case #{}] ", case.id))
+            .push(format!("Follow-up from {}: '", case.reporter_display()))
             .push(message)
-            .push("' from channel #")
-            .push(msg.channel_id.name(&ctx.cache).unwrap_or_default()),
+            .push("'"),
     )?;
 
-    // Delete initial message
-    msg.delete(&ctx.http)?;
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Your follow-up was added to case #{}.", case.id),
+    )?;
 
     Ok(())
 }
 
+// Open a case for a report, optionally hiding the reporter's identity
+fn file_report(ctx: &Context, msg: &Message, mut args: Args, anonymous: bool) -> CommandResult {
+    // Retrieve configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
+    // Get the reported message from a link or the message being replied to
+    let link = args.current().and_then(parse_message_link);
+    if link.is_some() {
+        args.advance();
+    }
+    let target = match link {
+        Some((guild, channel, message)) => {
+            // Direct messages can only report messages from the configured server
+            let expected = msg.guild_id.unwrap_or(GuildId(config.everyone_role.0));
+            if GuildId(guild) != expected {
+                msg.channel_id
+                    .say(&ctx.http, "You can only report messages from this server")?;
+                return Ok(());
+            }
+            Some((ChannelId(channel), MessageId(message)))
+        }
+        None => msg
+            .message_reference
+            .as_ref()
+            .and_then(|r| r.message_id.map(|m| (r.channel_id, m))),
+    };
+
+    // Get message from the remaining args, it may contain the delimiter
+    let message = args.rest().to_string();
+    if message.is_empty() && target.is_none() {
+        msg.channel_id
+            .say(&ctx.http, "Argument <message> not satisfied")?;
+        return Ok(());
+    }
+
+    // Retrieve the reported message before it can be deleted
+    let target = match target {
+        Some((channel, message)) => match channel.message(&ctx.http, message) {
+            Ok(target) => Some(target),
+            Err(_) => {
+                msg.channel_id
+                    .say(&ctx.http, "Unable to find the reported message")?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    // Open a case for the report
+    let mut case = cases::Case::new(
+        "report",
+        Some(msg.author.id.0),
+        msg.channel_id.0,
+        message.clone(),
+        Local::now().timestamp_millis(),
+    );
+    case.anonymous = anonymous;
+    let mut case = cases::create(&mut client, case)?;
+
+    // Send message to reports channel
+    let mut header = MessageBuilder::new();
+    header
+        .push_bold(format!("[Case #{}] ", case.id))
+        .push(case.reporter_display())
+        .push(" reported message '")
+        .push(message)
+        .push("'");
+    match msg.guild_id {
+        Some(_) => header
+            .push(" from channel #")
+            .push(msg.channel_id.name(&ctx.cache).unwrap_or_default()),
+        None => header.push(" through a direct message"),
+    };
+    let header = header.build();
+    match target {
+        Some(target) => {
+            case.evidence = Some(capture_evidence(
+                ctx,
+                config.reports_channel,
+                &target,
+                header,
+            )?);
+            cases::save(&mut client, &case)?;
+        }
+        None => {
+            config.reports_channel.say(&ctx.http, header)?;
+        }
+    }
+
+    // Delete initial message, which isn't possible in direct messages
+    if msg.guild_id.is_some() {
+        msg.delete(&ctx.http)?;
+    } else {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "Your report was received as case #{}.{} You can add more information with `~followup {}, <message>`.",
+                case.id,
+                if anonymous {
+                    " Your identity will not be shared with the moderators."
+                } else {
+                    ""
+                },
+                case.id
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Copy a message and its attachments into a channel so they survive deletion
+pub fn capture_evidence(
+    ctx: &Context,
+    channel: ChannelId,
+    target: &Message,
+    header: String,
+) -> serenity::Result<cases::Evidence> {
+    // Download the attachments to re-upload them, skipping any that are too large
+    let mut files = Vec::new();
+    for attachment in &target.attachments {
+        if attachment.size > MAX_ATTACHMENT_SIZE {
+            continue;
+        }
+        match attachment.download() {
+            Ok(data) => files.push((data, attachment.filename.clone())),
+            Err(e) => error!("Failed to download attachment '{}': {}", attachment.url, e),
+        }
+    }
+
+    let author = target.author.tag();
+    let channel_name = target.channel_id.name(&ctx.cache).unwrap_or_default();
+    let link = format!(
+        "https://discord.com/channels/{}/{}/{}",
+        target.guild_id.map(|g| g.0).unwrap_or_default(),
+        target.channel_id.0,
+        target.id.0
+    );
+    let sent = channel.send_files(
+        &ctx.http,
+        files
+            .iter()
+            .map(|(data, name)| (data.as_slice(), name.as_str())),
+        |m| {
+            m.content(&header).embed(|e| {
+                e.author(|a| a.name(&author).icon_url(target.author.face()))
+                    .description(&target.content)
+                    .field("Author", format!("<@{}>", target.author.id.0), true)
+                    .field("Channel", format!("#{}", channel_name), true)
+                    .field("Link", &link, false)
+                    .timestamp(&target.timestamp);
+
+                if let Some(edited) = target.edited_timestamp {
+                    e.field("Edited", edited.to_rfc2822(), true);
+                }
+                for attachment in &target.attachments {
+                    e.field("Attachment", &attachment.url, false);
+                }
+
+                e
+            })
+        },
+    )?;
+
+    // Prefer the copies of the attachments since the originals are removed on deletion
+    let mut attachments = sent
+        .attachments
+        .iter()
+        .map(|a| a.url.clone())
+        .collect::<Vec<_>>();
+    if attachments.len() < target.attachments.len() {
+        attachments = target.attachments.iter().map(|a| a.url.clone()).collect();
+    }
+
+    Ok(cases::Evidence {
+        author: target.author.id.0,
+        author_tag: author,
+        channel: target.channel_id.0,
+        message: target.id.0,
+        content: target.content.clone(),
+        attachments,
+        created_at: target.timestamp.timestamp_millis(),
+        edited_at: target.edited_timestamp.map(|t| t.timestamp_millis()),
+    })
+}
+
 #[command]
 #[help_available]
 #[description = "Send a report of immediate importance with an optional message. This pings the mods/admins"]
@@ -76,20 +320,31 @@ pub fn emergency(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandRes
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
-    // Retrieve channel
-    let channel = ctx
-        .http
-        .get_channel(REPORTS_CHANNEL_ID.clone())
-        .expect("Invalid channel ID");
+    // Retrieve configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
+    // Open a case for the emergency
+    let mut case = cases::create(
+        &mut client,
+        cases::Case::new(
+            "emergency",
+            Some(msg.author.id.0),
+            msg.channel_id.0,
+            message.clone(),
+            Local::now().timestamp_millis(),
+        ),
+    )?;
 
     // Send message to reports channel
-    channel.id().say(
+    let alert = config.reports_channel.say(
         &ctx.http,
         MessageBuilder::new()
+            .push_bold(format!("[Case #{}] ", case.id))
             .push("(")
-            .mention(&RoleId(MANAGER_ROLE_ID.clone()))
+            .mention(&config.manager_role)
             .push(" ")
-            .mention(&RoleId(MENTOR_ROLE_ID.clone()))
+            .mention(&config.mentor_role)
             .push(") ")
             .push_bold("EMERGENCY!! ")
             .mention(&msg.author)
@@ -97,7 +352,23 @@ pub fn emergency(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandRes
             .push(msg.channel_id.name(&ctx.cache).unwrap_or_default())
             .push(" with message '")
             .push(message)
-            .push("'"),
+            .push("'\n")
+            .push_italic(format!(
+                "React with {} or run `~mod ack {}` to acknowledge",
+                ACKNOWLEDGE_EMOJI, case.id
+            )),
+    )?;
+    alert.react(&ctx.http, ACKNOWLEDGE_EMOJI)?;
+    cases::set_alert_message(&mut client, &mut case, alert.id.0)?;
+
+    // Keep paging until someone acknowledges it
+    scheduler::schedule(
+        &mut client,
+        Utc::now().timestamp_millis() + REPAGE_INTERVAL,
+        &Job::Repage {
+            case: case.id,
+            attempt: 1,
+        },
     )?;
 
     // Delete initial message
@@ -105,3 +376,72 @@ pub fn emergency(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandRes
 
     Ok(())
 }
+
+// Ping the moderators again about an unacknowledged emergency, escalating to the on-call moderators
+pub fn repage(ctx: &Context, id: u64, attempt: u32) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let case = match cases::get(&mut client, id)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+
+    // Stop once someone is handling it
+    if case.status != cases::Status::Open {
+        return Ok(());
+    }
+
+    let config = config::load(&mut client)?;
+    config.reports_channel.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push_bold(format!("[Case #{}] ", case.id))
+            .push("(")
+            .mention(&config.manager_role)
+            .push(" ")
+            .mention(&config.mentor_role)
+            .push(") ")
+            .push_bold("The emergency from ")
+            .push(case.reporter_display())
+            .push_bold(" is still unacknowledged! ")
+            .push_italic(format!(
+                "React with {} on the original message or run `~mod ack {}`",
+                ACKNOWLEDGE_EMOJI, case.id
+            )),
+    )?;
+
+    // Notify the on-call moderators directly
+    if attempt == ESCALATE_AFTER {
+        for user in moderation::oncall(&mut client)? {
+            let sent = UserId(user).create_dm_channel(&ctx.http).and_then(|c| {
+                c.say(
+                    &ctx.http,
+                    MessageBuilder::new()
+                        .push_bold("EMERGENCY!! ")
+                        .push(format!(
+                            "Case #{} has been unacknowledged for {} minutes. Reported by ",
+                            case.id,
+                            attempt as i64 * REPAGE_INTERVAL / 60_000
+                        ))
+                        .push(case.reporter_display())
+                        .push(" with message '")
+                        .push(&case.message)
+                        .push("'"),
+                )
+            });
+            if let Err(e) = sent {
+                error!("Failed to page on-call moderator {}: {}", user, e);
+            }
+        }
+    }
+
+    scheduler::schedule(
+        &mut client,
+        Utc::now().timestamp_millis() + REPAGE_INTERVAL,
+        &Job::Repage {
+            case: case.id,
+            attempt: attempt + 1,
+        },
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,583 @@
+use chrono::prelude::*;
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::checks::{MANAGER_CHECK, RESPONDER_CHECK};
+use crate::commands::moderation::ACKNOWLEDGE_EMOJI;
+use crate::data::{cases, config, event, get_connection, moderation};
+use crate::util::{format_time, parse_user_id, truncate};
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Track the handling of reports"]
+#[usage = "<list|show|assign|reply|resolve>"]
+#[sub_commands(case_list, case_show, case_assign, case_reply, case_resolve)]
+pub fn case(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~mod case list [<status>]`, `~mod case show <id>`, `~mod case assign <id>, [<user>]`, `~mod case reply <id>, <message>`, or `~mod case resolve <id>, <notes>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available]
+#[checks(Manager)]
+#[description = "List all the cases with a status, defaulting to those not yet resolved"]
+#[usage = "[<open|acknowledged|resolved>]"]
+#[example = "open"]
+#[min_args(0)]
+#[max_args(1)]
+pub fn case_list(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the status filter from args
+    let status = match args.single::<cases::Status>() {
+        Ok(status) => Some(status),
+        Err(ArgError::Eos) => None,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <status>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Retrieve the matching cases, newest first
+    let mut client = get_connection(&ctx.data)?;
+    let matching = cases::all(&mut client)?
+        .into_iter()
+        .rev()
+        .filter(|c| match status {
+            Some(status) => c.status == status,
+            None => c.status != cases::Status::Resolved,
+        })
+        .collect::<Vec<_>>();
+
+    if matching.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "There are no matching cases")?;
+        return Ok(());
+    }
+
+    // Send table of cases
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.description(format!("Here are the {} matching cases:", matching.len()));
+
+            // Embeds are limited to 25 fields
+            for case in matching.iter().take(25) {
+                e.field(
+                    format!("#{} ({})", case.id, case.kind),
+                    format!(
+                        "**Status**: {}\n**Reporter**: {}\n**Assignee**: {}\n**Message**: {}",
+                        case.status,
+                        case.reporter_display(),
+                        format_user(case.assignee),
+                        truncate(&case.message, 100)
+                    ),
+                    true,
+                );
+            }
+
+            e
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("show")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Show the details of a case"]
+#[usage = "<id>"]
+#[example = "12"]
+#[num_args(1)]
+pub fn case_show(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let case = match parse_case(ctx, msg, &mut args, &mut client)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+    let timezone = event::timezone(&mut client)?;
+
+    // Describe the actions taken for the case
+    let mut actions = Vec::new();
+    for id in &case.actions {
+        if let Some(action) = moderation::get(&mut client, *id)? {
+            actions.push(format!(
+                "`#{}` {} <@{}>",
+                action.id, action.kind, action.user
+            ));
+        }
+    }
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title(format!("Case #{}", case.id))
+                .field("Kind", &case.kind, true)
+                .field("Status", case.status, true)
+                .field("Assignee", format_user(case.assignee), true)
+                .field("Reporter", case.reporter_display(), true)
+                .field("Channel", format!("<#{}>", case.channel), true)
+                .field(
+                    "Opened",
+                    format_time(case.created_at, timezone),
+                    true,
+                )
+                .field("Message", truncate(&case.message, 1024), false);
+
+            if let Some(evidence) = &case.evidence {
+                e.field(
+                    "Reported message",
+                    format!(
+                        "**Author**: <@{}> ({})\n**Channel**: <#{}>\n**Sent**: {}{}\n**Content**: {}",
+                        evidence.author,
+                        evidence.author_tag,
+                        evidence.channel,
+                        format_time(evidence.created_at, timezone),
+                        match evidence.edited_at {
+                            Some(at) => format!(
+                                "\n**Edited**: {}",
+                                format_time(at, timezone)
+                            ),
+                            None => String::new(),
+                        },
+                        truncate(&evidence.content, 700)
+                    ),
+                    false,
+                );
+                if !evidence.attachments.is_empty() {
+                    e.field(
+                        "Attachments",
+                        truncate(&evidence.attachments.join("\n"), 1024),
+                        false,
+                    );
+                }
+            }
+
+            // Show the most recent notes that fit
+            if !case.notes.is_empty() {
+                let notes = case
+                    .notes
+                    .iter()
+                    .rev()
+                    .take(5)
+                    .rev()
+                    .map(|n| {
+                        format!(
+                            "**{}**: {}",
+                            if n.from_reporter {
+                                "Reporter".to_string()
+                            } else {
+                                format!("<@{}>", n.author)
+                            },
+                            truncate(&n.content, 180)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                e.field("Notes", notes.join("\n"), false);
+            }
+
+            if !actions.is_empty() {
+                e.field("Actions", truncate(&actions.join("\n"), 1024), false);
+            }
+
+            if let Some(resolution) = &case.resolution {
+                e.field("Resolution", truncate(resolution, 1024), false);
+            }
+
+            e
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("assign")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Assign a case to a moderator, defaulting to yourself. This marks the case as acknowledged."]
+#[usage = "<id> [<user>]"]
+#[example = "12, @someone"]
+#[min_args(1)]
+#[max_args(2)]
+pub fn case_assign(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let mut case = match parse_case(ctx, msg, &mut args, &mut client)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+
+    // Get the assignee from args
+    let assignee = match args.single::<String>() {
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => id,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <user>: '{}'", raw),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => msg.author.id,
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Update the case
+    let was_open = case.status == cases::Status::Open;
+    case.assignee = Some(assignee.0);
+    if was_open {
+        case.status = cases::Status::Acknowledged;
+    }
+    case.updated_at = Local::now().timestamp_millis();
+    cases::save(&mut client, &case)?;
+
+    if was_open {
+        notify_reporter(ctx, &case);
+    }
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Assigned case ")
+            .push_mono(format!("#{}", case.id))
+            .push(" to ")
+            .mention(&assignee)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("reply")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Privately reply to the reporter of a case through the bot"]
+#[usage = "<id> <message>"]
+#[example = "12, could you tell us when this happened?"]
+#[min_args(2)]
+pub fn case_reply(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let mut case = match parse_case(ctx, msg, &mut args, &mut client)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+
+    // Cases the bot opened on its own have no one to reply to
+    let reporter = match case.reporter {
+        Some(reporter) => UserId(reporter),
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Case `#{}` was opened by the bot and has no reporter",
+                    case.id
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    // The message may contain the delimiter
+    let message = args.rest().to_string();
+
+    // Relay the message to the reporter
+    let sent = reporter
+        .create_dm_channel(&ctx.http)
+        .and_then(|c| {
+            c.say(
+                &ctx.http,
+                format!(
+                    "The moderators replied to your case #{}: '{}'\nYou can respond with `~followup {}, <message>`.",
+                    case.id, message, case.id
+                ),
+            )
+        });
+    if sent.is_err() {
+        msg.channel_id
+            .say(&ctx.http, "Unable to send a direct message to the reporter")?;
+        return Ok(());
+    }
+
+    case.notes.push(cases::Note {
+        author: msg.author.id.0,
+        from_reporter: false,
+        content: message,
+        at: Local::now().timestamp_millis(),
+    });
+    case.updated_at = Local::now().timestamp_millis();
+    cases::save(&mut client, &case)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Sent your reply to the reporter of case `#{}`.", case.id),
+    )?;
+
+    Ok(())
+}
+
+#[command("resolve")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Resolve a case with notes on how it was handled"]
+#[usage = "<id> <notes>"]
+#[example = "12, talked with both users and they apologized"]
+#[min_args(2)]
+pub fn case_resolve(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let mut case = match parse_case(ctx, msg, &mut args, &mut client)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+
+    // The notes may contain the delimiter
+    let notes = args.rest().to_string();
+    if notes.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Argument <notes> not satisfied")?;
+        return Ok(());
+    }
+
+    // Update the case
+    case.status = cases::Status::Resolved;
+    case.resolution = Some(notes);
+    if case.assignee.is_none() {
+        case.assignee = Some(msg.author.id.0);
+    }
+    case.updated_at = Local::now().timestamp_millis();
+    cases::save(&mut client, &case)?;
+
+    // Notify the other moderators
+    let config = config::load(&mut client)?;
+    config.reports_channel.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Case ")
+            .push_mono(format!("#{}", case.id))
+            .push(" was resolved by ")
+            .mention(&msg.author)
+            .build(),
+    )?;
+
+    if msg.channel_id != config.reports_channel {
+        msg.channel_id
+            .say(&ctx.http, format!("Resolved case `#{}`.", case.id))?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[help_available]
+#[checks(Responder)]
+#[description = "Acknowledge a case, letting the reporter know someone is handling it"]
+#[usage = "<id>"]
+#[example = "12"]
+#[num_args(1)]
+pub fn ack(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let case = match parse_case(ctx, msg, &mut args, &mut client)? {
+        Some(case) => case,
+        None => return Ok(()),
+    };
+
+    if case.status != cases::Status::Open {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("Case `#{}` is already {}", case.id, case.status),
+        )?;
+        return Ok(());
+    }
+
+    acknowledge(ctx, &mut client, case, msg.author.id)?;
+    Ok(())
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Set whether you are on-call to be paged directly for unacknowledged emergencies"]
+#[usage = "<on|off|list>"]
+#[example = "on"]
+#[num_args(1)]
+pub fn oncall(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+
+    match args.single::<String>()?.to_lowercase().as_str() {
+        "on" => {
+            moderation::set_oncall(&mut client, msg.author.id.0, true)?;
+            msg.channel_id.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .mention(&msg.author)
+                    .push(" is now on-call.")
+                    .build(),
+            )?;
+        }
+        "off" => {
+            moderation::set_oncall(&mut client, msg.author.id.0, false)?;
+            msg.channel_id.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .mention(&msg.author)
+                    .push(" is no longer on-call.")
+                    .build(),
+            )?;
+        }
+        "list" => {
+            let users = moderation::oncall(&mut client)?;
+            let list = if users.is_empty() {
+                "Nobody is on-call!".to_string()
+            } else {
+                users
+                    .iter()
+                    .map(|u| format!("<@{}>", u))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            msg.channel_id
+                .say(&ctx.http, format!("On-call moderators: {}", list))?;
+        }
+        other => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <on|off|list>: '{}'", other),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// Acknowledge a case when a moderator reacts to its alert
+pub fn acknowledge_reaction(ctx: &Context, reaction: &Reaction) -> CommandResult {
+    if reaction.emoji != ReactionType::Unicode(ACKNOWLEDGE_EMOJI.to_string()) {
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let case = match cases::find_by_alert_message(&mut client, reaction.message_id.0)? {
+        Some(case) if case.status == cases::Status::Open => case,
+        _ => return Ok(()),
+    };
+
+    // Only those that were paged can acknowledge it
+    let config = config::load(&mut client)?;
+    let guild_id = match reaction.guild_id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let member = guild_id.member(ctx, reaction.user_id)?;
+    if !member.roles.contains(&config.manager_role)
+        && !member.roles.contains(&config.mentor_role)
+        && !moderation::oncall(&mut client)?.contains(&reaction.user_id.0)
+    {
+        return Ok(());
+    }
+
+    acknowledge(ctx, &mut client, case, reaction.user_id)
+}
+
+// Mark a case as acknowledged and notify everyone involved
+fn acknowledge(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    mut case: cases::Case,
+    by: UserId,
+) -> CommandResult {
+    case.status = cases::Status::Acknowledged;
+    if case.assignee.is_none() {
+        case.assignee = Some(by.0);
+    }
+    case.updated_at = Local::now().timestamp_millis();
+    cases::save(client, &case)?;
+
+    let config = config::load(client)?;
+    config.reports_channel.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Case ")
+            .push_mono(format!("#{}", case.id))
+            .push(" was acknowledged by ")
+            .mention(&by)
+            .build(),
+    )?;
+
+    notify_reporter(ctx, &case);
+    Ok(())
+}
+
+// Privately let the reporter know that someone is handling their case
+fn notify_reporter(ctx: &Context, case: &cases::Case) {
+    let reporter = match case.reporter {
+        Some(reporter) => UserId(reporter),
+        None => return,
+    };
+    let sent = reporter.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            format!(
+                "A moderator is now handling your {} (case #{}). Thank you for letting us know.",
+                case.kind, case.id
+            ),
+        )
+    });
+
+    if let Err(e) = sent {
+        error!("Failed to notify reporter of case #{}: {}", case.id, e);
+    }
+}
+
+// Parse a case id from the args and retrieve it, replying if it couldn't be found
+fn parse_case(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+    client: &mut redis::Connection,
+) -> Result<Option<cases::Case>, CommandError> {
+    let raw = match args.single::<String>() {
+        Ok(raw) => raw,
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <id> not satisfied")?;
+            return Ok(None);
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Allow ids to be prefixed with '#'
+    let id = match raw.trim_start_matches('#').parse::<u64>() {
+        Ok(id) => id,
+        Err(why) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(None);
+        }
+    };
+
+    match cases::get(client, id)? {
+        Some(case) => Ok(Some(case)),
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Case `#{}` does not exist", id))?;
+            Ok(None)
+        }
+    }
+}
+
+// Format an optional user as a mention
+fn format_user(user: Option<u64>) -> String {
+    match user {
+        Some(id) => format!("<@{}>", id),
+        None => "nobody".to_string(),
+    }
+}
@@ -0,0 +1,350 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::{content_safe, ContentSafeOptions, MessageBuilder};
+
+use crate::commands::checks::MANAGER_CHECK;
+use crate::data::{config, event, get_connection, modmail, ratelimit};
+use crate::util::{format_duration, parse_user_id};
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Manage private conversations with participants. Participants start one by sending a direct message to the bot."]
+#[usage = "<close|reopen>"]
+#[sub_commands(modmail_close, modmail_reopen)]
+pub fn modmail(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~mod modmail close [<reason>]` or `~mod modmail reopen <user>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("close")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Close the conversation in this channel, archiving its transcript in the reports channel"]
+#[usage = "[<reason>]"]
+#[example = "question answered"]
+#[only_in("guilds")]
+#[min_args(0)]
+pub fn modmail_close(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
+    // Ensure this is a conversation channel
+    let user = match modmail::user_for(&mut client, msg.channel_id.0)? {
+        Some(user) => UserId(user),
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "This channel is not a modmail conversation")?;
+            return Ok(());
+        }
+    };
+
+    // Archive the transcript
+    let timezone = event::timezone(&mut client)?;
+    let transcript = transcript(ctx, msg.channel_id, timezone)?;
+    let filename = format!(
+        "modmail-{}-{}.txt",
+        user.0,
+        Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    let mut header = MessageBuilder::new();
+    header
+        .push("Modmail conversation with ")
+        .mention(&user)
+        .push(" closed by ")
+        .mention(&msg.author);
+    if !args.is_empty() {
+        header.push(": ").push_safe(args.rest());
+    }
+    let archived = config.reports_channel.send_files(
+        &ctx.http,
+        vec![(transcript.as_bytes(), filename.as_str())],
+        |m| m.content(header.build()),
+    )?;
+    modmail::set_transcript(&mut client, user.0, archived.id.0)?;
+
+    // Let the user know they can start over
+    let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            "The moderators have closed this conversation. Send another message here any time to start a new one.",
+        )
+    });
+    if let Err(e) = sent {
+        error!("Failed to notify user of closed modmail: {}", e);
+    }
+
+    modmail::close(&mut client, user.0, msg.channel_id.0)?;
+    msg.channel_id.delete(&ctx.http)?;
+
+    Ok(())
+}
+
+#[command("reopen")]
+#[help_available]
+#[checks(Manager)]
+#[description = "Start a conversation with a user, linking to the transcript of their previous one"]
+#[usage = "<user>"]
+#[example = "@someone"]
+#[only_in("guilds")]
+#[num_args(1)]
+pub fn modmail_reopen(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the user from args
+    let user = match args.single::<String>() {
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => id.to_user(&*ctx)?,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <user>: '{}'", raw),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <user> not satisfied")?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Point to the existing conversation if there is one
+    let mut client = get_connection(&ctx.data)?;
+    if let Some(channel) = modmail::channel_for(&mut client, user.id.0)? {
+        msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push("The conversation with ")
+                .mention(&user)
+                .push(" is already open in ")
+                .channel(ChannelId(channel))
+                .build(),
+        )?;
+        return Ok(());
+    }
+
+    let config = config::load(&mut client)?;
+    let channel = open_thread(ctx, &mut client, &config, &user)?;
+    user.direct_message(&ctx, |m| {
+        m.content("The moderators have started a conversation with you. Reply here to respond.")
+    })?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push("Opened a conversation with ")
+            .mention(&user)
+            .push(" in ")
+            .channel(channel)
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+// Relay a message between a user and their conversation channel
+pub fn relay(ctx: &Context, msg: &Message) -> CommandResult {
+    // Ignore the bot's own relayed messages and any commands
+    if msg.author.bot || msg.content.starts_with('~') {
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+
+    // Moderators replying in a conversation channel
+    if !msg.is_private() {
+        let user = match modmail::user_for(&mut client, msg.channel_id.0)? {
+            Some(user) => UserId(user),
+            None => return Ok(()),
+        };
+
+        let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+            c.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .push_bold("Moderators: ")
+                    .push(with_attachments(msg))
+                    .build(),
+            )
+        });
+        match sent {
+            Ok(_) => msg.react(ctx, "📨")?,
+            Err(_) => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    "Unable to deliver the message, the user may have direct messages disabled",
+                )?;
+            }
+        };
+        return Ok(());
+    }
+
+    // Users sending a direct message to the bot
+    let existing = modmail::channel_for(&mut client, msg.author.id.0)?;
+    let channel = match existing {
+        Some(channel) if ChannelId(channel).to_channel_cached(&ctx.cache).is_some() => {
+            ChannelId(channel)
+        }
+        _ => {
+            // Forget channels that were deleted by hand
+            if let Some(channel) = existing {
+                modmail::close(&mut client, msg.author.id.0, channel)?;
+            }
+
+            // Stop users from filling the server with conversation channels
+            let key = format!("modmail_threads:{}", msg.author.id.0);
+            let limit = ratelimit::get(&mut client, "modmail_threads")?;
+            let now = Utc::now().timestamp_millis();
+            if ratelimit::count(&mut client, &key, limit, now)? >= limit.count {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "You've started too many conversations with the moderators recently. Please try again in {}.",
+                        format_duration(limit.window)
+                    ),
+                )?;
+                return Ok(());
+            }
+            ratelimit::hit(&mut client, &key, limit, now)?;
+
+            let config = config::load(&mut client)?;
+            let channel = open_thread(ctx, &mut client, &config, &msg.author)?;
+            msg.channel_id.say(
+                &ctx.http,
+                "Your message was sent to the moderators. Their replies will appear here, and anything else you send will be passed along.",
+            )?;
+            channel
+        }
+    };
+
+    // Keep the user from pinging anyone in the server
+    let content = content_safe(
+        &ctx.cache,
+        with_attachments(msg),
+        &ContentSafeOptions::default(),
+    );
+    channel.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push_bold_safe(format!("{}: ", msg.author.tag()))
+            .push(content)
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+// Create a channel only the managers can see for a conversation with a user
+fn open_thread(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: &User,
+) -> Result<ChannelId, CommandError> {
+    // Keep the conversations next to the reports channel
+    let category = match config.reports_channel.to_channel_cached(&ctx.cache) {
+        Some(Channel::Guild(channel)) => channel.read().category_id,
+        _ => None,
+    };
+
+    // The everyone role shares its id with the guild
+    let guild_id = GuildId(config.everyone_role.0);
+    let mut overwrites = vec![
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.everyone_role),
+            allow: Permissions::empty(),
+            deny: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.manager_role),
+            allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+            deny: Permissions::empty(),
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.bot_role),
+            allow: Permissions::READ_MESSAGES
+                | Permissions::READ_MESSAGE_HISTORY
+                | Permissions::SEND_MESSAGES,
+            deny: Permissions::empty(),
+        },
+    ];
+    overwrites.extend(config.silenced());
+    let channel = guild_id.create_channel(&ctx.http, |c| {
+        let c = c
+            .name(format!("modmail-{}", user.name))
+            .kind(ChannelType::Text)
+            .topic(format!("Conversation with {} ({})", user.tag(), user.id.0))
+            .permissions(overwrites);
+        match category {
+            Some(category) => c.category(category),
+            None => c,
+        }
+    })?;
+    modmail::open(client, user.id.0, channel.id.0)?;
+
+    // Introduce the conversation, linking to the previous one
+    let mut intro = MessageBuilder::new();
+    intro
+        .push("Conversation with ")
+        .mention(user)
+        .push(". Messages sent here are relayed to them, except commands. Use `~mod modmail close [<reason>]` when done.");
+    if let Some(previous) = modmail::transcript(client, user.id.0)? {
+        intro.push(format!(
+            "\nPrevious transcript: https://discord.com/channels/{}/{}/{}",
+            guild_id.0, config.reports_channel.0, previous
+        ));
+    }
+    channel.say(&ctx.http, intro.build())?;
+
+    Ok(channel.id)
+}
+
+// Retrieve the full history of a channel as text, oldest first
+fn transcript(ctx: &Context, channel: ChannelId, timezone: Tz) -> Result<String, CommandError> {
+    let mut messages = Vec::new();
+    loop {
+        let batch = match messages.last().map(|m: &Message| m.id) {
+            Some(before) => channel.messages(&ctx.http, |r| r.before(before).limit(100))?,
+            None => channel.messages(&ctx.http, |r| r.limit(100))?,
+        };
+        if batch.is_empty() {
+            break;
+        }
+        messages.extend(batch);
+    }
+
+    Ok(messages
+        .iter()
+        .rev()
+        .map(|m| {
+            format!(
+                "[{}] {}: {}",
+                m.timestamp
+                    .with_timezone(&timezone)
+                    .format("%Y-%m-%d %H:%M:%S %Z"),
+                m.author.tag(),
+                with_attachments(m)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+// Get the content of a message with links to its attachments
+fn with_attachments(msg: &Message) -> String {
+    let mut content = msg.content.clone();
+    for attachment in &msg.attachments {
+        content.push('\n');
+        content.push_str(&attachment.url);
+    }
+    content
+}
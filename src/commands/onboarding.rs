@@ -0,0 +1,326 @@
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::data::{
+    get_connection, modmail,
+    onboarding::{self, Progress, STEPS},
+    profiles::{self, Profile},
+};
+use crate::util::parse_user_id;
+
+#[command]
+#[help_available]
+#[description = "Show your participant profile, or someone else's"]
+#[usage = "[<user>]"]
+#[example = "@someone"]
+#[max_args(1)]
+#[sub_commands(profile_edit)]
+pub fn profile(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the user from args, defaulting to the author
+    let user = match args.single::<String>() {
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => id,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <user>: '{}'", raw),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => msg.author.id,
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    let profile = match profiles::get(&mut client, user.0)? {
+        Some(profile) => profile,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "That user doesn't have a profile yet")?;
+            return Ok(());
+        }
+    };
+
+    let name = user.to_user(&*ctx)?.tag();
+    let unset = || "*not set*".to_string();
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title(format!("Profile of {}", name))
+                .field("Pronouns", profile.pronouns.unwrap_or_else(unset), true)
+                .field("Timezone", profile.timezone.unwrap_or_else(unset), true)
+                .field(
+                    "Skills",
+                    if profile.skills.is_empty() {
+                        unset()
+                    } else {
+                        profile.skills.join(", ")
+                    },
+                    false,
+                )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("edit")]
+#[help_available]
+#[description = "Answer the onboarding questions again to update your profile"]
+#[num_args(0)]
+pub fn profile_edit(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    if profiles::get(&mut client, msg.author.id.0)?.is_none() {
+        profiles::save(&mut client, msg.author.id.0, &Profile::default())?;
+    }
+    advance(ctx, &mut client, msg.author.id, 0)?;
+
+    if msg.guild_id.is_some() {
+        msg.channel_id.say(
+            &ctx.http,
+            "Check your direct messages to update your profile",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Configure the direct messages new members are sent"]
+#[usage = "<list|set|reset>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(onboarding_list, onboarding_set, onboarding_reset)]
+pub fn onboarding(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin onboarding list`, `~admin onboarding set <step>, <text|off>`, or `~admin onboarding reset <step>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available(false)]
+#[description = "List the onboarding steps and the text sent for each"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn onboarding_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let mut texts = Vec::new();
+    for step in 0..STEPS.len() {
+        texts.push(onboarding::text(&mut client, step)?);
+    }
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Onboarding steps");
+            for (step, text) in STEPS.iter().zip(texts) {
+                let name = if step.question.is_some() {
+                    format!("{} (question)", step.name)
+                } else {
+                    step.name.to_string()
+                };
+                let text = if text.is_empty() {
+                    "*disabled*".to_string()
+                } else {
+                    text
+                };
+                e.field(name, text, false);
+            }
+            e.footer(|f| {
+                f.text("Steps are sent in this order, waiting for a reply to each question")
+            })
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("set")]
+#[help_available(false)]
+#[description = "Change the text sent for an onboarding step, or disable it with `off`"]
+#[usage = "<step>, <text|off>"]
+#[example = "schedule, The schedule is at https://example.com/schedule"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(2)]
+pub fn onboarding_set(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let step = match parse_step(ctx, msg, &mut args)? {
+        Some(step) => step,
+        None => return Ok(()),
+    };
+    let text = args.rest().trim();
+
+    let mut client = get_connection(&ctx.data)?;
+    if text.eq_ignore_ascii_case("off") {
+        onboarding::set_text(&mut client, step, "")?;
+        msg.channel_id.say(
+            &ctx.http,
+            format!("Disabled the `{}` onboarding step.", STEPS[step].name),
+        )?;
+    } else {
+        onboarding::set_text(&mut client, step, text)?;
+        msg.channel_id.say(
+            &ctx.http,
+            format!("Updated the `{}` onboarding step.", STEPS[step].name),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[command("reset")]
+#[help_available(false)]
+#[description = "Go back to the default text for an onboarding step"]
+#[usage = "<step>"]
+#[example = "welcome"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn onboarding_reset(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let step = match parse_step(ctx, msg, &mut args)? {
+        Some(step) => step,
+        None => return Ok(()),
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    onboarding::reset_text(&mut client, step)?;
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Reset the `{}` onboarding step.", STEPS[step].name),
+    )?;
+
+    Ok(())
+}
+
+// Start onboarding a member unless they already have a profile
+pub fn start(ctx: &Context, client: &mut redis::Connection, user: UserId) -> CommandResult {
+    if profiles::get(client, user.0)?.is_some() {
+        return Ok(());
+    }
+
+    profiles::save(client, user.0, &Profile::default())?;
+    advance(ctx, client, user, 0)
+}
+
+// Treat the first direct message a member being onboarded sends after a question as
+// their answer. Returns whether the message was handled.
+pub fn handle_reply(ctx: &Context, msg: &Message) -> Result<bool, CommandError> {
+    if !msg.is_private() || msg.author.bot || msg.content.starts_with('~') {
+        return Ok(false);
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let progress = match onboarding::progress(&mut client, msg.author.id.0)? {
+        Some(progress) => progress,
+        None => return Ok(false),
+    };
+    let question = match STEPS.get(progress.step).and_then(|s| s.question) {
+        Some(question) => question,
+        None => {
+            onboarding::finish(&mut client, msg.author.id.0)?;
+            return Ok(false);
+        }
+    };
+
+    // Conversations with the moderators go through modmail
+    if modmail::channel_for(&mut client, msg.author.id.0)?.is_some() {
+        return Ok(false);
+    }
+
+    // Anything after the member's first message since the question isn't an answer
+    let since = msg
+        .channel_id
+        .messages(&ctx.http, |r| r.after(MessageId(progress.prompt)).limit(10))?;
+    let first = since
+        .iter()
+        .filter(|m| m.author.id == msg.author.id)
+        .map(|m| m.id)
+        .min();
+    if first != Some(msg.id) {
+        onboarding::finish(&mut client, msg.author.id.0)?;
+        return Ok(false);
+    }
+
+    if !msg.content.trim().eq_ignore_ascii_case("skip") {
+        let mut profile = profiles::get(&mut client, msg.author.id.0)?.unwrap_or_default();
+        question.answer(&mut profile, &msg.content);
+        profiles::save(&mut client, msg.author.id.0, &profile)?;
+    }
+
+    advance(ctx, &mut client, msg.author.id, progress.step + 1)?;
+
+    Ok(true)
+}
+
+// Send the onboarding steps starting from the given one until reaching a question
+fn advance(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    user: UserId,
+    mut step: usize,
+) -> CommandResult {
+    let channel = user.create_dm_channel(&ctx.http)?;
+
+    while step < STEPS.len() {
+        let text = onboarding::text(client, step)?;
+        if text.is_empty() {
+            step += 1;
+            continue;
+        }
+
+        let prompt = match channel.say(&ctx.http, text) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                error!(
+                    "Failed to send onboarding step '{}': {}",
+                    STEPS[step].name, e
+                );
+                break;
+            }
+        };
+        if STEPS[step].question.is_some() {
+            onboarding::set_progress(
+                client,
+                user.0,
+                &Progress {
+                    step,
+                    prompt: prompt.id.0,
+                },
+            )?;
+            return Ok(());
+        }
+        step += 1;
+    }
+
+    onboarding::finish(client, user.0)?;
+    Ok(())
+}
+
+// Get the onboarding step from args, replying if it is invalid
+fn parse_step(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> Result<Option<usize>, CommandError> {
+    let name = args.single::<String>()?.to_lowercase();
+    match onboarding::find(&name) {
+        Some(step) => Ok(Some(step)),
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <step>: '{}' is not one of {}",
+                    name,
+                    STEPS.iter().map(|s| s.name).collect::<Vec<_>>().join(", ")
+                ),
+            )?;
+            Ok(None)
+        }
+    }
+}
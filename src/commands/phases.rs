@@ -0,0 +1,209 @@
+use chrono::prelude::*;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::data::{
+    config, event, get_connection,
+    phases::{self, Phase},
+};
+use crate::scheduler::{self, Job};
+use crate::util::{format_time, parse_time};
+
+#[command]
+#[help_available(false)]
+#[description = "Show the current event phase and any scheduled transitions"]
+#[usage = "[<set|schedule|unschedule>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+#[sub_commands(phase_set, phase_schedule, phase_unschedule)]
+pub fn phase(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let current = phases::get(&mut client)?;
+    let timezone = event::timezone(&mut client)?;
+    let scheduled = scheduler::pending(&mut client)?
+        .into_iter()
+        .filter_map(|(at, job)| match job {
+            Job::Phase { phase } => Some((at, phase)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Event phase")
+                .field(
+                    "Current",
+                    match current {
+                        Some(phase) => phase.to_string(),
+                        None => "*not set, all commands are allowed*".to_string(),
+                    },
+                    false,
+                )
+                .field(
+                    "Scheduled",
+                    if scheduled.is_empty() {
+                        "*none*".to_string()
+                    } else {
+                        scheduled
+                            .iter()
+                            .map(|(at, phase)| {
+                                format!("**{}** at {}", phase, format_time(*at, timezone))
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    },
+                    false,
+                )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("set")]
+#[help_available(false)]
+#[description = "Move the event into a phase immediately"]
+#[usage = "<phase>"]
+#[example = "hacking"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn phase_set(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let phase = match args.rest().parse::<Phase>() {
+        Ok(phase) => phase,
+        Err(why) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <phase>: {}", why),
+            )?;
+            return Ok(());
+        }
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if phases::get(&mut client)? == Some(phase) {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("The event is already in the {} phase", phase),
+        )?;
+        return Ok(());
+    }
+
+    transition(ctx, phase)?;
+    msg.channel_id.say(
+        &ctx.http,
+        format!("The event is now in the {} phase.", phase),
+    )?;
+
+    Ok(())
+}
+
+#[command("schedule")]
+#[help_available(false)]
+#[description = "Schedule the event to move into a phase at a time, replacing any earlier schedule for that phase. The time is either relative or a date and time in the event timezone."]
+#[usage = "<phase>, <time>"]
+#[example = "hacking, in 2h"]
+#[example = "judging, 2020-10-25 12:00"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(2)]
+pub fn phase_schedule(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the phase and time from args
+    let phase = match args.single::<Phase>() {
+        Ok(phase) => phase,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <phase>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let now = Utc::now().timestamp_millis();
+    let at = match parse_time(args.rest(), now, timezone) {
+        Some(at) if at > now => at,
+        _ => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <time>: '{}' must be in the future, like `in 2h` or `2020-10-25 12:00`",
+                    args.rest()
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    // Scheduling the same job again moves it to the new time
+    scheduler::schedule(&mut client, at, &Job::Phase { phase })?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!(
+            "The event will move into the {} phase at {}.",
+            phase,
+            format_time(at, timezone)
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[command("unschedule")]
+#[help_available(false)]
+#[description = "Cancel a scheduled transition into a phase"]
+#[usage = "<phase>"]
+#[example = "judging"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn phase_unschedule(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let phase = match args.rest().parse::<Phase>() {
+        Ok(phase) => phase,
+        Err(why) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <phase>: {}", why),
+            )?;
+            return Ok(());
+        }
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    if scheduler::cancel(&mut client, &Job::Phase { phase })? {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("Cancelled the transition into the {} phase.", phase),
+        )?;
+    } else {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("No transition into the {} phase is scheduled", phase),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Move the event into a phase and announce it
+pub fn transition(ctx: &Context, phase: Phase) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    phases::set(&mut client, phase)?;
+
+    if let Some(channel) = config.announcements_channel {
+        channel.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("Phase: {}", phase))
+                    .description(phase.announcement())
+            })
+        })?;
+    }
+
+    Ok(())
+}
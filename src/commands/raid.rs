@@ -0,0 +1,188 @@
+use chrono::prelude::*;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::{checks::MANAGER_CHECK, verify};
+use crate::data::{config, get_connection, moderation, ratelimit};
+use crate::util::{format_duration, parse_user_id};
+
+// Accounts younger than this are reported when they join
+const MIN_ACCOUNT_AGE: i64 = 7 * 86_400_000;
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Check or change whether the server is in lockdown. During a lockdown, new members are quarantined instead of being let in."]
+#[usage = "<on|off|status>"]
+#[example = "off"]
+#[num_args(1)]
+pub fn lockdown(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+
+    match args.single::<String>()?.to_lowercase().as_str() {
+        "on" if config::load(&mut client)?.quarantine_role.is_none() => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Lockdowns are not configured, set `quarantine_role` with `~admin config set` first",
+            )?;
+        }
+        "on" => {
+            moderation::set_lockdown(&mut client, true)?;
+            msg.channel_id.say(
+                &ctx.http,
+                "The server is now in lockdown. New members will be quarantined until it is lifted with `~mod lockdown off`.",
+            )?;
+        }
+        "off" => {
+            moderation::set_lockdown(&mut client, false)?;
+            let quarantined = moderation::quarantined(&mut client)?.len();
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "The lockdown has been lifted. {} members are still quarantined, use `~mod release <user|all>` to let them in.",
+                    quarantined
+                ),
+            )?;
+        }
+        "status" => {
+            let status = if moderation::lockdown(&mut client)? {
+                "in lockdown"
+            } else {
+                "not in lockdown"
+            };
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "The server is {} and {} members are quarantined.",
+                    status,
+                    moderation::quarantined(&mut client)?.len()
+                ),
+            )?;
+        }
+        other => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <on|off|status>: '{}' is not one of on, off, or status",
+                    other
+                ),
+            )?;
+        }
+    };
+
+    Ok(())
+}
+
+#[command]
+#[help_available]
+#[checks(Manager)]
+#[description = "Let a quarantined member, or all of them, into the server"]
+#[usage = "<user|all>"]
+#[example = "@someone"]
+#[num_args(1)]
+pub fn release(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+
+    // Get the members to release from args
+    let users = match args.single::<String>() {
+        Ok(raw) if raw.eq_ignore_ascii_case("all") => moderation::quarantined(&mut client)?,
+        Ok(raw) => match parse_user_id(&raw) {
+            Some(id) => vec![id.0],
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("Failed parsing argument <user>: '{}'", raw),
+                )?;
+                return Ok(());
+            }
+        },
+        Err(ArgError::Eos) => {
+            msg.channel_id
+                .say(&ctx.http, "Argument <user> not satisfied")?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Remove the quarantine role and let them in as usual
+    let config = config::load(&mut client)?;
+    let mut released = 0;
+    for user in users {
+        if !moderation::remove_quarantined(&mut client, user)? {
+            continue;
+        }
+
+        // The member may have left in the meantime
+        let guild_id = config.everyone_role.0;
+        let removed = match config.quarantine_role {
+            Some(role) => ctx.http.remove_member_role(guild_id, user, role.0).is_ok(),
+            None => true,
+        };
+        if removed && verify::admit(ctx, &mut client, &config, UserId(user)).is_ok() {
+            released += 1;
+        }
+    }
+
+    msg.channel_id
+        .say(&ctx.http, format!("Released {} members.", released))?;
+
+    Ok(())
+}
+
+// Give a new member their role, watching for raids
+pub fn member_joined(ctx: &Context, member: &mut Member) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    let user = member.user.read().clone();
+    let now = Utc::now().timestamp_millis();
+
+    // Lock the server down when too many members join at once, if members can be quarantined
+    let limit = ratelimit::get(&mut client, "joins")?;
+    let joins = ratelimit::hit(&mut client, "joins", limit, now)?;
+    let mut lockdown = moderation::lockdown(&mut client)?;
+    if joins == limit.count + 1 && !lockdown && config.quarantine_role.is_some() {
+        moderation::set_lockdown(&mut client, true)?;
+        lockdown = true;
+
+        config.reports_channel.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .mention(&config.manager_role)
+                .push_bold(" Possible raid: ")
+                .push(format!(
+                    "more than {} members joined within {}. The server is now in lockdown and new members are being quarantined. Use `~mod lockdown off` to lift it.",
+                    limit.count,
+                    format_duration(limit.window)
+                ))
+                .build(),
+        )?;
+    }
+
+    // Report accounts that were created very recently
+    let age = now - user.created_at().timestamp_millis();
+    if age < MIN_ACCOUNT_AGE {
+        let age = match age / 3_600_000 {
+            0 => "less than an hour".to_string(),
+            hours => format_duration(hours * 3_600_000),
+        };
+        config.reports_channel.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .mention(&user)
+                .push(format!(" joined with an account created {} ago", age))
+                .build(),
+        )?;
+    }
+
+    match config.quarantine_role {
+        Some(role) if lockdown => {
+            member.add_role(&ctx.http, role)?;
+            moderation::add_quarantined(&mut client, user.id.0)?;
+        }
+        _ => verify::admit(ctx, &mut client, &config, user.id)?,
+    }
+
+    Ok(())
+}
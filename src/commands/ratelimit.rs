@@ -0,0 +1,386 @@
+use chrono::prelude::*;
+use log::error;
+use serenity::framework::standard::{
+    macros::command, ArgError, Args, Command, CommandError, CommandGroup, CommandResult,
+};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::actions;
+use crate::data::{
+    cases, config, get_connection,
+    moderation::ActionKind,
+    ratelimit::{self, Limit},
+};
+use crate::util::format_duration;
+
+// How long users are muted for repeatedly exceeding a rate limit
+const MUTE_DURATION: i64 = 10 * 60_000;
+
+/// Every way each command can be typed, along with its full path like `workshop cancel`
+pub struct CommandPaths;
+
+impl TypeMapKey for CommandPaths {
+    type Value = Vec<(Vec<String>, String)>;
+}
+
+#[command]
+#[help_available(false)]
+#[description = "View and modify the rate limits for messages and commands"]
+#[usage = "<list|set>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(ratelimit_list, ratelimit_set)]
+pub fn ratelimit(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin ratelimit list` or `~admin ratelimit set <name>, <count>/<window>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("list")]
+#[help_available(false)]
+#[description = "List all the rate limits in effect"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn ratelimit_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let limits = ratelimit::all(&mut client)?;
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Rate limits")
+                .description(
+                    limits
+                        .iter()
+                        .map(|(name, limit)| format!("**{}**: {}", name, limit))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+                .footer(|f| f.text("Commands without their own limit share the `commands` limit"))
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("set")]
+#[help_available(false)]
+#[description = "Set the rate limit for messages per channel, all commands, or a specific command"]
+#[usage = "<messages|commands|command name>, <count>/<window>"]
+#[example = "messages, 5/10s"]
+#[example = "emergency, 1/5m"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(2)]
+pub fn ratelimit_set(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Get the name and limit from args
+    let name = args.single::<String>()?.to_lowercase();
+    let words = name.split_whitespace().collect::<Vec<_>>();
+    let limit = match args.single::<Limit>() {
+        Ok(limit) => limit,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <limit>: {}", why),
+            )?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    // Only limits that are checked can be set, and commands are stored under their full path
+    let name = if ratelimit::DEFAULTS.iter().any(|(n, _)| *n == name) {
+        name
+    } else {
+        let data = ctx.data.read();
+        let paths = data
+            .get::<CommandPaths>()
+            .expect("Failed to get command paths");
+        match find_path(paths, &words) {
+            Some((matched, path)) if matched == words.len() => path.to_string(),
+            _ => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <name>: '{}' must be one of {} or a command",
+                        name,
+                        ratelimit::DEFAULTS
+                            .iter()
+                            .map(|(n, _)| format!("`{}`", n))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )?;
+                return Ok(());
+            }
+        }
+    };
+
+    let mut client = get_connection(&ctx.data)?;
+    ratelimit::set(&mut client, &name, limit)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Set the `{}` rate limit to {}.", name, limit),
+    )?;
+
+    Ok(())
+}
+
+// Check whether a user may run a command, escalating if they run it too often
+pub fn allow_command(ctx: &Context, msg: &Message, name: &str) -> bool {
+    match check_command(ctx, msg, name) {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            error!("Failed to check rate limit for '{}': {:?}", name, e);
+            true
+        }
+    }
+}
+
+fn check_command(ctx: &Context, msg: &Message, name: &str) -> Result<bool, CommandError> {
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    if is_exempt(&config, msg) {
+        return Ok(true);
+    }
+
+    // Subcommands share names like `cancel`, so limit them by their full path
+    let path = {
+        let content = msg.content.trim_start();
+        let content = match content.strip_prefix('~') {
+            Some(rest) => rest,
+            // Skip the mention the command was invoked with
+            None => content.find('>').map_or(content, |end| &content[end + 1..]),
+        };
+        let words = content.split_whitespace().collect::<Vec<_>>();
+        let data = ctx.data.read();
+        match data
+            .get::<CommandPaths>()
+            .and_then(|paths| find_path(paths, &words))
+        {
+            Some((_, path)) => path.to_string(),
+            None => name.to_string(),
+        }
+    };
+    let name = path.as_str();
+
+    let limit = ratelimit::get(&mut client, name)?;
+    let count = ratelimit::hit(
+        &mut client,
+        &format!("command:{}:{}", name, msg.author.id.0),
+        limit,
+        Utc::now().timestamp_millis(),
+    )?;
+    if count <= limit.count {
+        return Ok(true);
+    }
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!(
+            "You can only use `~{}` {} times every {}. Please wait before trying again.",
+            name,
+            limit.count,
+            format_duration(limit.window)
+        ),
+    )?;
+
+    // Only escalate once each time the limit is exceeded
+    if count == limit.count + 1 {
+        escalate(
+            ctx,
+            &mut client,
+            &config,
+            msg,
+            format!("using `~{}` too often", name),
+        )?;
+    }
+
+    Ok(false)
+}
+
+// List every way each command in the groups can be typed along with its full path,
+// which uses the longest prefix of its group and the first name of each command
+pub fn command_paths(groups: &[&'static CommandGroup]) -> Vec<(Vec<String>, String)> {
+    let mut paths = Vec::new();
+    for group in groups {
+        let prefixes = group.options.prefixes;
+        let typed = if prefixes.is_empty() {
+            vec![Vec::new()]
+        } else {
+            prefixes.iter().map(|p| vec![p.to_string()]).collect()
+        };
+        let path = prefixes
+            .iter()
+            .max_by_key(|p| p.len())
+            .map(|p| vec![p.to_string()])
+            .unwrap_or_default();
+
+        for command in group.options.commands {
+            add_command_paths(&mut paths, &typed, &path, command);
+        }
+    }
+    paths
+}
+
+fn add_command_paths(
+    paths: &mut Vec<(Vec<String>, String)>,
+    typed: &[Vec<String>],
+    path: &[String],
+    command: &'static Command,
+) {
+    let names = command.options.names;
+    let typed = typed
+        .iter()
+        .flat_map(|t| {
+            names.iter().map(move |name| {
+                let mut t = t.clone();
+                t.push(name.to_string());
+                t
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut path = path.to_vec();
+    path.push(names[0].to_string());
+
+    for t in &typed {
+        paths.push((t.clone(), path.join(" ")));
+    }
+    for sub in command.options.sub_commands {
+        add_command_paths(paths, &typed, &path, sub);
+    }
+}
+
+// Find the longest command path the words start with, returning how many words it spans
+fn find_path<'a>(paths: &'a [(Vec<String>, String)], words: &[&str]) -> Option<(usize, &'a str)> {
+    paths
+        .iter()
+        .filter(|(typed, _)| {
+            typed.len() <= words.len() && typed.iter().zip(words).all(|(t, w)| t == w)
+        })
+        .max_by_key(|(typed, _)| typed.len())
+        .map(|(typed, path)| (typed.len(), path.as_str()))
+}
+
+// Delete messages from users flooding a channel, escalating if they continue.
+// Returns whether the message was allowed.
+pub fn check_message(ctx: &Context, msg: &Message) -> Result<bool, CommandError> {
+    if msg.author.bot || msg.guild_id.is_none() {
+        return Ok(true);
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    if is_exempt(&config, msg) {
+        return Ok(true);
+    }
+
+    let limit = ratelimit::get(&mut client, "messages")?;
+    let count = ratelimit::hit(
+        &mut client,
+        &format!("channel:{}:{}", msg.channel_id.0, msg.author.id.0),
+        limit,
+        Utc::now().timestamp_millis(),
+    )?;
+    if count <= limit.count {
+        return Ok(true);
+    }
+
+    msg.delete(ctx)?;
+    if count == limit.count + 1 {
+        escalate(
+            ctx,
+            &mut client,
+            &config,
+            msg,
+            format!(
+                "sending messages too quickly in #{}",
+                msg.channel_id.name(&ctx.cache).unwrap_or_default()
+            ),
+        )?;
+    }
+
+    Ok(false)
+}
+
+// Respond to a user exceeding a rate limit with a warning, then a mute, then a case
+fn escalate(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    msg: &Message,
+    violation: String,
+) -> CommandResult {
+    let bot_id = ctx.cache.read().user.id;
+    let reason = format!("Rate limit exceeded: {}", violation);
+
+    match ratelimit::strike(client, msg.author.id.0)? {
+        1 => {
+            actions::apply(
+                ctx,
+                client,
+                config,
+                ActionKind::Warn,
+                msg.author.id,
+                bot_id,
+                reason,
+                None,
+                None,
+            )?;
+        }
+        // Skip straight to a case if members can't be muted
+        2 if config.muted_role.is_some() => {
+            actions::apply(
+                ctx,
+                client,
+                config,
+                ActionKind::Mute,
+                msg.author.id,
+                bot_id,
+                reason,
+                Some(MUTE_DURATION),
+                None,
+            )?;
+        }
+        _ => {
+            let case = cases::create(
+                client,
+                cases::Case::new(
+                    "rate limit",
+                    None,
+                    msg.channel_id.0,
+                    reason.clone(),
+                    Local::now().timestamp_millis(),
+                ),
+            )?;
+            config.reports_channel.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .push_bold(format!("[Case #{}] ", case.id))
+                    .mention(&msg.author)
+                    .push(" keeps exceeding rate limits after being warned and muted: ")
+                    .push(violation)
+                    .build(),
+            )?;
+        }
+    };
+
+    Ok(())
+}
+
+// Mentors and managers are trusted
+fn is_exempt(config: &config::Config, msg: &Message) -> bool {
+    match &msg.member {
+        Some(member) => {
+            member.roles.contains(&config.mentor_role)
+                || member.roles.contains(&config.manager_role)
+        }
+        None => false,
+    }
+}
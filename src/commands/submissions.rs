@@ -0,0 +1,279 @@
+use chrono::prelude::*;
+use redis::Commands;
+use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::data::{
+    event, get_connection, judging,
+    submissions::{self, Submission},
+};
+use crate::util::{format_time, parse_time, truncate};
+
+#[command]
+#[help_available]
+#[description = "Submit your table's project, or update it until the deadline. Enter tracks with `~submit tracks`."]
+#[usage = "<link>, <title>"]
+#[example = "https://devpost.com/software/example, Example Project"]
+#[min_args(1)]
+#[sub_commands(submit_tracks)]
+pub fn submit(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    // Get the link and title from args, splitting on the first comma so titles can contain commas
+    let mut parts = args.rest().trim().splitn(2, ',');
+    let link = parts.next().unwrap_or_default().trim().to_string();
+    if !link.starts_with("https://") && !link.starts_with("http://") {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "Failed parsing argument <link>: '{}' must be a link to your Devpost or GitHub",
+                link
+            ),
+        )?;
+        return Ok(());
+    }
+    let title = parts.next().unwrap_or_default().trim().to_string();
+    if title.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Argument <title> not satisfied")?;
+        return Ok(());
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let (table, deadline) = match open_table(ctx, msg, &mut client)? {
+        Some(open) => open,
+        None => return Ok(()),
+    };
+
+    // Keep the tracks entered so far
+    let existing = submissions::get(&mut client, &table)?;
+    let updated = existing.is_some();
+    let tracks = existing.map(|s| s.tracks).unwrap_or_default();
+    submissions::save(
+        &mut client,
+        &Submission {
+            table: table.clone(),
+            link,
+            title: title.clone(),
+            submitted_by: msg.author.id.0,
+            updated_at: Utc::now().timestamp_millis(),
+            tracks,
+        },
+    )?;
+
+    let mut reply = MessageBuilder::new();
+    reply
+        .push(if updated { "Updated " } else { "Submitted " })
+        .push_bold_safe(&title)
+        .push(" for ")
+        .push_mono(&table)
+        .push(".");
+    if !updated && !judging::tracks(&mut client)?.is_empty() {
+        reply.push(" Enter any tracks with `~submit tracks <track>...`.");
+    }
+    if let Some(deadline) = deadline {
+        let timezone = event::timezone(&mut client)?;
+        reply.push(format!(
+            " It can be changed until {}.",
+            format_time(deadline, timezone)
+        ));
+    }
+    msg.channel_id.say(&ctx.http, reply.build())?;
+
+    Ok(())
+}
+
+#[command("tracks")]
+#[help_available]
+#[description = "Set the tracks your table's project is entering, replacing any entered before. Leave them out to enter none."]
+#[usage = "[<track>...]"]
+#[example = "Best Hardware Hack, Best Beginner Hack"]
+pub fn submit_tracks(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    // Every track entered must be one the organizers set up
+    let mut client = get_connection(&ctx.data)?;
+    let available = judging::tracks(&mut client)?;
+    let mut tracks = Vec::new();
+    for raw in args.iter::<String>() {
+        let raw = raw?;
+        match available
+            .iter()
+            .find(|t| t.eq_ignore_ascii_case(raw.trim()))
+        {
+            Some(track) if !tracks.contains(track) => tracks.push(track.clone()),
+            Some(_) => {}
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!(
+                        "Failed parsing argument <track>: '{}' must be one of {}",
+                        raw.trim(),
+                        if available.is_empty() {
+                            "no tracks, as none have been set up".to_string()
+                        } else {
+                            available.join(", ")
+                        }
+                    ),
+                )?;
+                return Ok(());
+            }
+        }
+    }
+
+    let table = match open_table(ctx, msg, &mut client)? {
+        Some((table, _)) => table,
+        None => return Ok(()),
+    };
+    let mut submission = match submissions::get(&mut client, &table)? {
+        Some(submission) => submission,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Your table must submit its project with `~submit <link>, <title>` before entering tracks",
+            )?;
+            return Ok(());
+        }
+    };
+    submission.tracks = tracks;
+    submission.submitted_by = msg.author.id.0;
+    submission.updated_at = Utc::now().timestamp_millis();
+    submissions::save(&mut client, &submission)?;
+
+    let mut reply = MessageBuilder::new();
+    reply.push_bold_safe(&submission.title);
+    if submission.tracks.is_empty() {
+        reply.push(" isn't entered in any tracks.");
+    } else {
+        reply.push(format!(" is entered in {}.", submission.tracks.join(", ")));
+    }
+    msg.channel_id.say(&ctx.http, reply.build())?;
+
+    Ok(())
+}
+
+#[command("submissions")]
+#[help_available(false)]
+#[description = "List the project each table submitted and the tables that haven't submitted"]
+#[usage = "[<deadline>]"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+#[sub_commands(submissions_deadline)]
+pub fn submissions_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let members: HashMap<u64, String> = client.hgetall("tables")?;
+    let submitted = submissions::all(&mut client)?;
+
+    // Sort tables by their number
+    let mut tables = members
+        .values()
+        .chain(submitted.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    submissions::sort_tables(&mut tables);
+
+    let (done, missing): (Vec<_>, Vec<_>) = tables.iter().partition(|t| submitted.contains_key(*t));
+    let done = done
+        .iter()
+        .map(|t| {
+            let s = &submitted[*t];
+            format!("**{}**: [{}]({})", t, s.title, s.link)
+        })
+        .collect::<Vec<_>>();
+    let missing = missing.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Submissions")
+                .field(
+                    format!("Submitted ({})", done.len()),
+                    list_field(&done.join("\n")),
+                    false,
+                )
+                .field(
+                    format!("Not submitted ({})", missing.len()),
+                    list_field(&missing.join(", ")),
+                    false,
+                )
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("deadline")]
+#[help_available(false)]
+#[description = "Set when submissions are locked. The time is either relative or a date and time in the event timezone."]
+#[usage = "<time>"]
+#[example = "2020-10-25 09:00"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[min_args(1)]
+pub fn submissions_deadline(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let at = match parse_time(args.rest(), Utc::now().timestamp_millis(), timezone) {
+        Some(at) => at,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "Failed parsing argument <time>: '{}' must be like `in 2h` or `2020-10-25 09:00`",
+                    args.rest()
+                ),
+            )?;
+            return Ok(());
+        }
+    };
+
+    submissions::set_deadline(&mut client, at)?;
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Submissions will lock at {}.", format_time(at, timezone)),
+    )?;
+
+    Ok(())
+}
+
+// Fit a list in an embed field, which can't be empty
+fn list_field(text: &str) -> String {
+    if text.is_empty() {
+        return "*none*".to_string();
+    }
+    truncate(text, 1024)
+}
+
+// Get the author's table and the submission deadline, replying if they can't submit
+fn open_table(
+    ctx: &Context,
+    msg: &Message,
+    client: &mut redis::Connection,
+) -> Result<Option<(String, Option<i64>)>, CommandError> {
+    // Lock submissions once the deadline has passed
+    let deadline = submissions::deadline(client)?;
+    if let Some(deadline) = deadline {
+        if Utc::now().timestamp_millis() >= deadline {
+            let timezone = event::timezone(client)?;
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Submissions closed at {}", format_time(deadline, timezone)),
+            )?;
+            return Ok(None);
+        }
+    }
+
+    // Submissions belong to the whole table
+    let table: Option<String> = client.hget("tables", msg.author.id.0)?;
+    match table {
+        Some(table) => Ok(Some((table, deadline))),
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                "You must join a table with `~join <table number>` before submitting",
+            )?;
+            Ok(None)
+        }
+    }
+}
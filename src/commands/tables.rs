@@ -4,11 +4,9 @@ use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils::MessageBuilder;
 
-use crate::data::get_connection;
-use crate::util::{
-    random_color, BOT_ROLE_ID, EVERYONE_ROLE_ID, MANAGER_ROLE_ID, MENTOR_ROLE_ID,
-    TABLES_CATEGORY_ID, TEAMLESS_ROLE_ID,
-};
+use crate::commands::checks::ACCEPTED_CHECK;
+use crate::data::{config, get_connection};
+use crate::util::random_color;
 
 #[command]
 #[help_available]
@@ -16,6 +14,7 @@ use crate::util::{
 #[usage = "<table_number>"]
 #[example = "1"]
 #[num_args(1)]
+#[checks(Accepted)]
 pub fn join(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     // Parse team number from args
     let team_num = match args.single::<i64>() {
@@ -35,10 +34,14 @@ pub fn join(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
+    // Retrieve redis connection and configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
     // Check if current user part of team
     if !msg
         .author
-        .has_role(&ctx.http, msg.guild_id.unwrap(), *TEAMLESS_ROLE_ID)?
+        .has_role(&ctx.http, msg.guild_id.unwrap(), config.teamless_role)?
     {
         msg.channel_id.say(
             &ctx.http,
@@ -81,7 +84,7 @@ pub fn join(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
     // Add current user to role and remove from teamless role
     let mut member = guild.read().member(&ctx.http, msg.author.id)?;
     member.add_role(&ctx.http, role.id)?;
-    member.remove_role(&ctx.http, *TEAMLESS_ROLE_ID)?;
+    member.remove_role(&ctx.http, config.teamless_role)?;
 
     // Create table if exists
     if guild
@@ -89,51 +92,49 @@ pub fn join(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
         .channel_id_from_name(&ctx.cache, format!("table-{}", team_num))
         .is_none()
     {
+        // Sending is granted by each role's own permissions so the silenced roles' denies apply
+        let mut overwrites = vec![
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(role.id),
+                allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::empty(),
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(config.everyone_role),
+                allow: Permissions::empty(),
+                deny: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(config.mentor_role),
+                allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::empty(),
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(config.manager_role),
+                allow: Permissions::READ_MESSAGES
+                    | Permissions::READ_MESSAGE_HISTORY
+                    | Permissions::MANAGE_MESSAGES,
+                deny: Permissions::empty(),
+            },
+            PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(config.bot_role),
+                allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+                deny: Permissions::empty(),
+            },
+        ];
+        overwrites.extend(config.silenced());
+
         guild.read().create_channel(&ctx.http, |c| {
             c.name(format!("table-{}", team_num))
                 .kind(ChannelType::Text)
                 .topic(format!("Private discussion space for Table {}", team_num))
-                .category(*TABLES_CATEGORY_ID)
+                .category(config.tables_category)
                 .nsfw(false)
-                .permissions(vec![
-                    PermissionOverwrite {
-                        kind: PermissionOverwriteType::Role(role.id),
-                        allow: Permissions::READ_MESSAGES
-                            | Permissions::READ_MESSAGE_HISTORY
-                            | Permissions::SEND_MESSAGES,
-                        deny: Permissions::empty(),
-                    },
-                    PermissionOverwrite {
-                        kind: PermissionOverwriteType::Role(RoleId(*EVERYONE_ROLE_ID)),
-                        allow: Permissions::empty(),
-                        deny: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
-                    },
-                    PermissionOverwrite {
-                        kind: PermissionOverwriteType::Role(RoleId(*MENTOR_ROLE_ID)),
-                        allow: Permissions::READ_MESSAGES
-                            | Permissions::READ_MESSAGE_HISTORY
-                            | Permissions::SEND_MESSAGES,
-                        deny: Permissions::empty(),
-                    },
-                    PermissionOverwrite {
-                        kind: PermissionOverwriteType::Role(RoleId(*MANAGER_ROLE_ID)),
-                        allow: Permissions::READ_MESSAGES
-                            | Permissions::READ_MESSAGE_HISTORY
-                            | Permissions::SEND_MESSAGES
-                            | Permissions::MANAGE_MESSAGES,
-                        deny: Permissions::empty(),
-                    },
-                    PermissionOverwrite {
-                        kind: PermissionOverwriteType::Role(RoleId(*BOT_ROLE_ID)),
-                        allow: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
-                        deny: Permissions::empty(),
-                    },
-                ])
+                .permissions(overwrites)
         })?;
     }
 
-    // Retrieve redis connection to persistently cache user's team
-    let mut client = get_connection(&ctx.data)?;
+    // Persistently cache user's team
     client.hset("tables", msg.author.id.0, format!("Table {}", team_num))?;
 
     // Send confirmation message
@@ -176,10 +177,14 @@ pub fn leave(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult
         Err(e) => return Err(CommandError(e.to_string())),
     };
 
+    // Retrieve redis connection and configuration
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+
     // Check user has a team in general
     if msg
         .author
-        .has_role(&ctx.http, msg.guild_id.unwrap(), *TEAMLESS_ROLE_ID)?
+        .has_role(&ctx.http, msg.guild_id.unwrap(), config.teamless_role)?
     {
         msg.channel_id.say(
             &ctx.http,
@@ -214,10 +219,9 @@ pub fn leave(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult
     // Remove user from role and add teamless role
     let mut member = guild.read().member(&ctx.http, msg.author.id)?;
     member.remove_role(&ctx.http, role.id)?;
-    member.add_role(&ctx.http, *TEAMLESS_ROLE_ID)?;
+    member.add_role(&ctx.http, config.teamless_role)?;
 
     // Remove user's team from redis cache
-    let mut client = get_connection(&ctx.data)?;
     client.hdel("tables", msg.author.id.0)?;
 
     // Send confirmation message
@@ -0,0 +1,243 @@
+use chrono::Utc;
+use log::error;
+use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::commands::conduct;
+use crate::data::{config, get_connection, moderation, ratelimit, roster};
+use crate::util::format_duration;
+
+#[command]
+#[help_available]
+#[description = "Verify your registration with the ticket code from your confirmation email. This can also be sent to the bot in a direct message."]
+#[usage = "<code>"]
+#[example = "ABC123"]
+#[num_args(1)]
+pub fn verify(ctx: &mut Context, msg: &Message, args: Args) -> CommandResult {
+    // Keep the code private
+    if msg.guild_id.is_some() {
+        msg.delete(&ctx.http)?;
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    let config = config::load(&mut client)?;
+    let reply = check_code(ctx, &mut client, &config, &msg.author, args.rest())?;
+    msg.author.direct_message(&*ctx, |m| m.content(reply))?;
+
+    Ok(())
+}
+
+#[command]
+#[help_available(false)]
+#[description = "Manage the roster of registrants members verify against"]
+#[usage = "<import|status>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(roster_import, roster_status)]
+pub fn roster(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin roster import` (with the CSV attached) or `~admin roster status`",
+    )?;
+
+    Ok(())
+}
+
+#[command("import")]
+#[help_available(false)]
+#[description = "Import registrants from an attached CSV with the columns name, email, and ticket code. Once a roster is imported, new members must verify before joining."]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn roster_import(ctx: &mut Context, msg: &Message) -> CommandResult {
+    // Retrieve the attached roster
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please attach the roster to the message")?;
+            return Ok(());
+        }
+    };
+    let raw = String::from_utf8_lossy(&attachment.download()?).to_string();
+    let registrants = match roster::parse(&raw) {
+        Ok(registrants) => registrants,
+        Err(why) => {
+            msg.channel_id.say(&ctx.http, why)?;
+            return Ok(());
+        }
+    };
+
+    // The roster contains personal information
+    msg.delete(&ctx.http)?;
+
+    let mut client = get_connection(&ctx.data)?;
+    roster::import(&mut client, &registrants)?;
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Imported {} registrants.", registrants.len()),
+    )?;
+
+    Ok(())
+}
+
+#[command("status")]
+#[help_available(false)]
+#[description = "Show how many registrants have verified"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(0)]
+pub fn roster_status(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (total, claimed) = roster::counts(&mut client)?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("{} of {} registrants have verified.", claimed, total),
+    )?;
+
+    Ok(())
+}
+
+// Let a member into the server, requiring them to verify first if there is a roster
+pub fn admit(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: UserId,
+) -> CommandResult {
+    let guild_id = config.everyone_role.0;
+
+    // Verification is turned off until the unverified role is configured
+    let unverified_role = match config.unverified_role {
+        Some(role) => role,
+        None => return conduct::let_in(ctx, client, config, user),
+    };
+    if !roster::is_enabled(client)? || roster::claimed_by(client, user.0)?.is_some() {
+        return conduct::let_in(ctx, client, config, user);
+    }
+
+    ctx.http
+        .add_member_role(guild_id, user.0, unverified_role.0)?;
+    roster::add_pending(client, user.0)?;
+
+    let sent = user.create_dm_channel(&ctx.http).and_then(|c| {
+        c.say(
+            &ctx.http,
+            "Welcome! Please reply with the ticket code from your registration confirmation email to get access to the server.",
+        )
+    });
+    if let Err(e) = sent {
+        error!("Failed to send verification prompt: {}", e);
+    }
+
+    Ok(())
+}
+
+// Treat direct messages from unverified members as their ticket code.
+// Returns whether the message was handled.
+pub fn handle_reply(ctx: &Context, msg: &Message) -> Result<bool, CommandError> {
+    if !msg.is_private() || msg.author.bot {
+        return Ok(false);
+    }
+
+    let mut client = get_connection(&ctx.data)?;
+    if !roster::is_pending(&mut client, msg.author.id.0)? {
+        return Ok(false);
+    }
+
+    let config = config::load(&mut client)?;
+    let reply = check_code(ctx, &mut client, &config, &msg.author, &msg.content)?;
+    msg.channel_id.say(&ctx.http, reply)?;
+
+    Ok(true)
+}
+
+// Verify a member with a ticket code, returning the reply for them
+fn check_code(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: &User,
+    code: &str,
+) -> Result<String, CommandError> {
+    if roster::claimed_by(client, user.id.0)?.is_some() {
+        return Ok("You have already verified your registration.".to_string());
+    }
+
+    // Quarantined members verify once a moderator releases them
+    if moderation::is_quarantined(client, user.id.0)? {
+        return Ok(
+            "You can't verify while you're quarantined. You'll be asked for your ticket code once a moderator lets you in."
+                .to_string(),
+        );
+    }
+
+    // Members let in before the roster was imported don't need to verify
+    if !roster::is_pending(client, user.id.0)? {
+        return Ok("You don't need to verify, you already have access to the server.".to_string());
+    }
+
+    // Stop codes from being guessed
+    let attempts = format!("verification:{}", user.id.0);
+    let limit = ratelimit::get(client, "verification")?;
+    let now = Utc::now().timestamp_millis();
+    if ratelimit::count(client, &attempts, limit, now)? >= limit.count {
+        return Ok(format!(
+            "You've entered too many incorrect ticket codes. Please try again in {} or contact an organizer.",
+            format_duration(limit.window)
+        ));
+    }
+
+    let registrant = match roster::get(client, code)? {
+        Some(registrant) => registrant,
+        None => {
+            ratelimit::hit(client, &attempts, limit, now)?;
+            return Ok(
+                "That ticket code doesn't match any registration. Please check your confirmation email and try again."
+                    .to_string(),
+            );
+        }
+    };
+    if !roster::claim(client, code, user.id.0)? {
+        ratelimit::hit(client, &attempts, limit, now)?;
+        return Ok(
+            "That ticket code has already been used. Please contact an organizer if you think this is a mistake."
+                .to_string(),
+        );
+    }
+
+    // Give the code back if they can't be let in so they can try again
+    let guild_id = GuildId(config.everyone_role.0);
+    if let Err(e) = swap_roles(ctx, client, config, user.id) {
+        roster::unclaim(client, code, user.id.0)?;
+        return Err(e);
+    }
+    roster::remove_pending(client, user.id.0)?;
+
+    // Nicknames are limited to 32 characters
+    let nickname = registrant.name.chars().take(32).collect::<String>();
+    if let Err(e) = guild_id.edit_member(&ctx.http, user.id, |m| m.nickname(&nickname)) {
+        error!("Failed to set nickname of '{}': {}", user.name, e);
+    }
+
+    Ok(format!(
+        "Thanks {}, your registration is verified!",
+        registrant.name
+    ))
+}
+
+// Swap the unverified role for the regular one
+fn swap_roles(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    user: UserId,
+) -> CommandResult {
+    if let Some(role) = config.unverified_role {
+        ctx.http
+            .remove_member_role(config.everyone_role.0, user.0, role.0)?;
+    }
+    conduct::let_in(ctx, client, config, user)
+}
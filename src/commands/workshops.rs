@@ -0,0 +1,607 @@
+use chrono::prelude::*;
+use log::error;
+use serenity::framework::standard::{macros::command, ArgError, Args, CommandError, CommandResult};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use serenity::utils::MessageBuilder;
+
+use crate::commands::checks::ACCEPTED_CHECK;
+use crate::data::{
+    agenda::{self, Item},
+    config, event, get_connection,
+    workshops::{self, Workshop},
+};
+use crate::scheduler::{self, Job};
+use crate::util::{format_duration, format_time, parse_user_id, truncate};
+
+// How long before a workshop starts attendees are reminded
+const REMINDER_LEAD: i64 = 15 * 60_000;
+
+#[command("list")]
+#[help_available]
+#[description = "List the upcoming workshops and how many seats are left"]
+#[num_args(0)]
+pub fn workshop_list(ctx: &mut Context, msg: &Message) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let timezone = event::timezone(&mut client)?;
+    let now = Utc::now().timestamp_millis();
+
+    let mut lines = Vec::new();
+    for workshop in workshops::all(&mut client)? {
+        let item = match agenda::get(&mut client, workshop.id)? {
+            Some(item) if item.end > now => item,
+            _ => continue,
+        };
+        let attending = workshops::attendees(&mut client, workshop.id)?.len();
+        let waiting = workshops::waitlist(&mut client, workshop.id)?.len();
+        lines.push((
+            item.start,
+            format!(
+                "`#{}` **{}** {}, {} of {} seats taken{}",
+                item.id,
+                item.title,
+                format_time(item.start, timezone),
+                attending,
+                workshop.capacity,
+                if waiting > 0 {
+                    format!(" and {} waiting", waiting)
+                } else {
+                    String::new()
+                }
+            ),
+        ));
+    }
+    if lines.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "There are no upcoming workshops")?;
+        return Ok(());
+    }
+    lines.sort_by_key(|(start, _)| *start);
+
+    msg.channel_id.send_message(&ctx.http, |m| {
+        m.embed(|e| {
+            e.title("Workshops")
+                .description(
+                    lines
+                        .into_iter()
+                        .map(|(_, line)| line)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+                .footer(|f| f.text("Use `~workshop rsvp <id>` to sign up"))
+        })
+    })?;
+
+    Ok(())
+}
+
+#[command("rsvp")]
+#[help_available]
+#[checks(Accepted)]
+#[description = "Sign up for a workshop. If it is full, you are added to the waitlist and signed up automatically when a seat opens."]
+#[usage = "<id>"]
+#[example = "4"]
+#[num_args(1)]
+pub fn workshop_rsvp(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (workshop, item) = match parse_workshop(ctx, msg, &mut args, &mut client)? {
+        Some(workshop) => workshop,
+        None => return Ok(()),
+    };
+    let user = msg.author.id.0;
+    let now = Utc::now().timestamp_millis();
+
+    if item.end <= now {
+        msg.channel_id
+            .say(&ctx.http, format!("**{}** has already ended", item.title))?;
+        return Ok(());
+    }
+    let attendees = workshops::attendees(&mut client, workshop.id)?;
+    if attendees.contains(&user) {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("You're already signed up for **{}**", item.title),
+        )?;
+        return Ok(());
+    }
+    if let Some(position) = workshops::waitlist(&mut client, workshop.id)?
+        .iter()
+        .position(|u| *u == user)
+    {
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "You're already number {} on the waitlist for **{}**",
+                position + 1,
+                item.title
+            ),
+        )?;
+        return Ok(());
+    }
+
+    // Take a seat if there is one, otherwise wait for one
+    if workshops::take_seat(&mut client, workshop.id, user, workshop.capacity, now)? {
+        let config = config::load(&mut client)?;
+        ctx.http
+            .add_member_role(config.everyone_role.0, user, workshop.role)?;
+        msg.channel_id.say(
+            &ctx.http,
+            MessageBuilder::new()
+                .push(format!("You're signed up for **{}**! ", item.title))
+                .push("You now have access to ")
+                .channel(ChannelId(workshop.channel))
+                .push(" and will get a reminder before it starts.")
+                .build(),
+        )?;
+    } else {
+        let position = workshops::add_waitlisted(&mut client, workshop.id, user, now)?;
+        msg.channel_id.say(
+            &ctx.http,
+            format!(
+                "**{}** is full, so you're number {} on the waitlist. You'll be signed up automatically if a seat opens.",
+                item.title, position
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[command("cancel")]
+#[help_available]
+#[description = "Give up your seat for a workshop, or leave its waitlist"]
+#[usage = "<id>"]
+#[example = "4"]
+#[num_args(1)]
+pub fn workshop_cancel(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (workshop, item) = match parse_workshop(ctx, msg, &mut args, &mut client)? {
+        Some(workshop) => workshop,
+        None => return Ok(()),
+    };
+    let user = msg.author.id.0;
+
+    // Seats stop mattering once the workshop is over
+    if item.end <= Utc::now().timestamp_millis() {
+        msg.channel_id
+            .say(&ctx.http, format!("**{}** has already ended", item.title))?;
+        return Ok(());
+    }
+
+    if workshops::remove_attendee(&mut client, workshop.id, user)? {
+        let config = config::load(&mut client)?;
+        ctx.http
+            .remove_member_role(config.everyone_role.0, user, workshop.role)?;
+        msg.channel_id.say(
+            &ctx.http,
+            format!("You're no longer signed up for **{}**.", item.title),
+        )?;
+
+        promote(ctx, &mut client, &config, &workshop, &item)?;
+    } else if workshops::remove_waitlisted(&mut client, workshop.id, user)? {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("You're no longer on the waitlist for **{}**.", item.title),
+        )?;
+    } else {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("You're not signed up for **{}**", item.title),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[command("attendees")]
+#[help_available]
+#[description = "Get the list of attendees for a workshop you're hosting through a direct message"]
+#[usage = "<id>"]
+#[example = "4"]
+#[num_args(1)]
+pub fn workshop_attendees(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (workshop, item) = match parse_workshop(ctx, msg, &mut args, &mut client)? {
+        Some(workshop) => workshop,
+        None => return Ok(()),
+    };
+
+    // Only the host and managers can see who is attending
+    let config = config::load(&mut client)?;
+    let allowed = workshop.host == msg.author.id.0
+        || msg.author.has_role(
+            &ctx.http,
+            GuildId(config.everyone_role.0),
+            config.manager_role,
+        )?;
+    if !allowed {
+        msg.channel_id.say(
+            &ctx.http,
+            "Only the host can see who is attending this workshop",
+        )?;
+        return Ok(());
+    }
+
+    send_attendees(ctx, &mut client, msg.author.id, &workshop, &item)?;
+    if msg.guild_id.is_some() {
+        msg.channel_id.say(
+            &ctx.http,
+            "Sent you the attendee list through a direct message.",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[command("workshop")]
+#[help_available(false)]
+#[description = "Manage the workshops participants sign up for"]
+#[usage = "<create|remove>"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[sub_commands(workshop_create, workshop_remove)]
+pub fn workshop_admin(ctx: &mut Context, msg: &Message) -> CommandResult {
+    msg.channel_id.say(
+        &ctx.http,
+        "Please use one of `~admin workshop create <agenda id>, <host>, <capacity>` or `~admin workshop remove <id>`",
+    )?;
+
+    Ok(())
+}
+
+#[command("create")]
+#[help_available(false)]
+#[description = "Open an item on the schedule for signups, creating a role and channel for its attendees"]
+#[usage = "<agenda id>, <host>, <capacity>"]
+#[example = "4, @someone, 30"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(3)]
+pub fn workshop_create(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+
+    // Get the agenda item, host, and capacity from args
+    let id = match args.single::<u64>() {
+        Ok(id) => id,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(());
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+    let item = match agenda::get(&mut client, id)? {
+        Some(item) => item,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Item `#{}` is not on the schedule", id))?;
+            return Ok(());
+        }
+    };
+    if workshops::get(&mut client, id)?.is_some() {
+        msg.channel_id.say(
+            &ctx.http,
+            format!("**{}** is already a workshop", item.title),
+        )?;
+        return Ok(());
+    }
+    let raw = args.single::<String>()?;
+    let host = match parse_user_id(&raw) {
+        Some(host) => host,
+        None => {
+            msg.channel_id.say(
+                &ctx.http,
+                format!("Failed parsing argument <host>: '{}'", raw),
+            )?;
+            return Ok(());
+        }
+    };
+    let capacity = match args.single::<usize>() {
+        Ok(capacity) if capacity > 0 => capacity,
+        _ => {
+            msg.channel_id.say(
+                &ctx.http,
+                "Failed parsing argument <capacity>: must be a positive number",
+            )?;
+            return Ok(());
+        }
+    };
+
+    // Give attendees access to a private channel through a role
+    let config = config::load(&mut client)?;
+    let guild_id = GuildId(config.everyone_role.0);
+    let role = guild_id.create_role(&ctx.http, |r| {
+        r.name(format!("Workshop: {}", item.title))
+            .mentionable(false)
+            .permissions(Permissions::empty())
+    })?;
+    // Sending is granted by each role's own permissions so the silenced roles' denies apply
+    let access =
+        Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY | Permissions::CONNECT;
+    let talk = Permissions::SEND_MESSAGES | Permissions::SPEAK;
+    let mut overwrites = vec![
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.everyone_role),
+            allow: Permissions::empty(),
+            deny: Permissions::READ_MESSAGES | Permissions::READ_MESSAGE_HISTORY,
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(role.id),
+            allow: access,
+            deny: Permissions::empty(),
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Member(host),
+            allow: access | talk | Permissions::MANAGE_MESSAGES,
+            deny: Permissions::empty(),
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.manager_role),
+            allow: access | Permissions::MANAGE_MESSAGES,
+            deny: Permissions::empty(),
+        },
+        PermissionOverwrite {
+            kind: PermissionOverwriteType::Role(config.bot_role),
+            allow: access | talk,
+            deny: Permissions::empty(),
+        },
+    ];
+    overwrites.extend(config.silenced());
+    let channel = guild_id.create_channel(&ctx.http, |c| {
+        c.name(format!("workshop-{}", item.id))
+            .kind(ChannelType::Text)
+            .topic(format!("Attendees of {}", item.title))
+            .permissions(overwrites)
+    })?;
+
+    let workshop = Workshop {
+        id: item.id,
+        host: host.0,
+        capacity,
+        role: role.id.0,
+        channel: channel.id.0,
+    };
+    workshops::save(&mut client, &workshop)?;
+
+    // Remind attendees before it starts and revoke access once it's over
+    let now = Utc::now().timestamp_millis();
+    if item.start - REMINDER_LEAD > now {
+        scheduler::schedule(
+            &mut client,
+            item.start - REMINDER_LEAD,
+            &Job::WorkshopReminder {
+                workshop: workshop.id,
+            },
+        )?;
+    }
+    scheduler::schedule(
+        &mut client,
+        item.end.max(now),
+        &Job::WorkshopEnd {
+            workshop: workshop.id,
+        },
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        MessageBuilder::new()
+            .push(format!(
+                "**{}** is open for signups with {} seats, hosted by ",
+                item.title, capacity
+            ))
+            .mention(&host)
+            .push(". Attendees get access to ")
+            .channel(channel.id)
+            .push(".")
+            .build(),
+    )?;
+
+    Ok(())
+}
+
+#[command("remove")]
+#[help_available(false)]
+#[description = "Close a workshop, deleting its role and channel"]
+#[usage = "<id>"]
+#[example = "4"]
+#[only_in("guilds")]
+#[required_permissions("ADMINISTRATOR")]
+#[num_args(1)]
+pub fn workshop_remove(ctx: &mut Context, msg: &Message, mut args: Args) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (workshop, item) = match parse_workshop(ctx, msg, &mut args, &mut client)? {
+        Some(workshop) => workshop,
+        None => return Ok(()),
+    };
+
+    // The role is already gone if the workshop is over
+    let config = config::load(&mut client)?;
+    if let Err(e) = GuildId(config.everyone_role.0).delete_role(&ctx.http, workshop.role) {
+        error!("Failed to delete workshop role: {}", e);
+    }
+    ChannelId(workshop.channel).delete(&ctx.http)?;
+    workshops::remove(&mut client, workshop.id)?;
+    scheduler::cancel(
+        &mut client,
+        &Job::WorkshopReminder {
+            workshop: workshop.id,
+        },
+    )?;
+    scheduler::cancel(
+        &mut client,
+        &Job::WorkshopEnd {
+            workshop: workshop.id,
+        },
+    )?;
+
+    msg.channel_id.say(
+        &ctx.http,
+        format!("Closed the **{}** workshop.", item.title),
+    )?;
+
+    Ok(())
+}
+
+// Remind attendees a workshop is starting soon and send the host who is coming
+pub fn remind(ctx: &Context, id: u64) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let (workshop, item) = match (
+        workshops::get(&mut client, id)?,
+        agenda::get(&mut client, id)?,
+    ) {
+        (Some(workshop), Some(item)) => (workshop, item),
+        _ => return Ok(()),
+    };
+
+    for user in workshops::attendees(&mut client, id)? {
+        let sent = UserId(user).create_dm_channel(&ctx.http).and_then(|c| {
+            c.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .push(format!(
+                        "**{}** starts in {} in ",
+                        item.title,
+                        format_duration(REMINDER_LEAD)
+                    ))
+                    .channel(ChannelId(workshop.channel))
+                    .push(". If you can't make it anymore, use `~workshop cancel ")
+                    .push(item.id)
+                    .push("` to give your seat to someone on the waitlist.")
+                    .build(),
+            )
+        });
+        if let Err(e) = sent {
+            error!("Failed to send workshop reminder to {}: {}", user, e);
+        }
+    }
+
+    send_attendees(ctx, &mut client, UserId(workshop.host), &workshop, &item)
+}
+
+// Revoke access to a workshop once it's over
+pub fn end(ctx: &Context, id: u64) -> CommandResult {
+    let mut client = get_connection(&ctx.data)?;
+    let workshop = match workshops::get(&mut client, id)? {
+        Some(workshop) => workshop,
+        None => return Ok(()),
+    };
+
+    let config = config::load(&mut client)?;
+    GuildId(config.everyone_role.0).delete_role(&ctx.http, workshop.role)?;
+
+    Ok(())
+}
+
+// Give open seats to the people waiting for them
+fn promote(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    config: &config::Config,
+    workshop: &Workshop,
+    item: &Item,
+) -> CommandResult {
+    while let Some(user) = workshops::promote_waitlisted(
+        client,
+        workshop.id,
+        workshop.capacity,
+        Utc::now().timestamp_millis(),
+    )? {
+        // The member may have left in the meantime
+        if let Err(e) = ctx
+            .http
+            .add_member_role(config.everyone_role.0, user, workshop.role)
+        {
+            error!("Failed to give workshop role to {}: {}", user, e);
+            continue;
+        }
+
+        let sent = UserId(user).create_dm_channel(&ctx.http).and_then(|c| {
+            c.say(
+                &ctx.http,
+                MessageBuilder::new()
+                    .push(format!(
+                        "A seat opened up, so you're now signed up for **{}**! You have access to ",
+                        item.title
+                    ))
+                    .channel(ChannelId(workshop.channel))
+                    .push(".")
+                    .build(),
+            )
+        });
+        if let Err(e) = sent {
+            error!("Failed to notify {} of their workshop seat: {}", user, e);
+        }
+    }
+
+    Ok(())
+}
+
+// Send the list of attendees and the waitlist to someone
+fn send_attendees(
+    ctx: &Context,
+    client: &mut redis::Connection,
+    to: UserId,
+    workshop: &Workshop,
+    item: &Item,
+) -> CommandResult {
+    let describe = |users: Vec<u64>| {
+        if users.is_empty() {
+            return "*none*".to_string();
+        }
+        users
+            .iter()
+            .enumerate()
+            .map(|(i, user)| {
+                let tag = UserId(*user)
+                    .to_user_cached(&ctx.cache)
+                    .map(|u| u.read().tag())
+                    .unwrap_or_default();
+                format!("{}. <@{}> {}", i + 1, user, tag)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let attendees = workshops::attendees(client, workshop.id)?;
+    let count = attendees.len();
+    let attendees = describe(attendees);
+    let waitlist = describe(workshops::waitlist(client, workshop.id)?);
+
+    to.create_dm_channel(&ctx.http)?
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("Attendees of {}", item.title))
+                    .description(format!("{} of {} seats taken", count, workshop.capacity))
+                    .field("Attending", truncate(&attendees, 1024), false)
+                    .field("Waitlist", truncate(&waitlist, 1024), false)
+            })
+        })?;
+
+    Ok(())
+}
+
+// Get a workshop and its agenda item from args, replying if it is invalid
+fn parse_workshop(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+    client: &mut redis::Connection,
+) -> Result<Option<(Workshop, Item)>, CommandError> {
+    let id = match args.single::<u64>() {
+        Ok(id) => id,
+        Err(ArgError::Parse(why)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Failed parsing argument <id>: {}", why))?;
+            return Ok(None);
+        }
+        Err(e) => return Err(CommandError(e.to_string())),
+    };
+
+    match (workshops::get(client, id)?, agenda::get(client, id)?) {
+        (Some(workshop), Some(item)) => Ok(Some((workshop, item))),
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, format!("Workshop `#{}` does not exist", id))?;
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use chrono_tz::Tz;
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{parse_time, split_csv_line};
+
+/// Something happening during the event
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub id: u64,
+    pub title: String,
+    pub start: i64,
+    pub end: i64,
+    pub location: String,
+    pub description: String,
+}
+
+// Parse an agenda from CSV with the columns title, start, end, location, and description,
+// where times are in the event timezone
+pub fn parse(raw: &str, timezone: Tz, now: i64) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+
+    for (number, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        if fields.len() != 5 {
+            return Err(format!(
+                "Line {} has {} columns, but title, start, end, location, and description are required",
+                number + 1,
+                fields.len()
+            ));
+        }
+
+        // Skip the header if there is one
+        if number == 0 && fields[1].trim().eq_ignore_ascii_case("start") {
+            continue;
+        }
+
+        let (start, end) = match (
+            parse_time(&fields[1], now, timezone),
+            parse_time(&fields[2], now, timezone),
+        ) {
+            (Some(start), Some(end)) if start < end => (start, end),
+            _ => {
+                return Err(format!(
+                    "Line {} must have a start before its end, like `2020-10-24 09:00`",
+                    number + 1
+                ))
+            }
+        };
+        items.push(Item {
+            id: 0,
+            title: fields[0].trim().to_string(),
+            start,
+            end,
+            location: fields[3].trim().to_string(),
+            description: fields[4].trim().to_string(),
+        });
+    }
+
+    Ok(items)
+}
+
+// Persist a new item, assigning it an id
+pub fn add(client: &mut Connection, mut item: Item) -> RedisResult<Item> {
+    item.id = client.incr("agenda_item_id", 1)?;
+    let raw = serde_json::to_string(&item).expect("Failed to serialize agenda item");
+    let _: () = client.hset("agenda", item.id, raw)?;
+    Ok(item)
+}
+
+// Get an item by its id
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<Item>> {
+    let raw: Option<String> = client.hget("agenda", id)?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Delete an item and its reminders, returning whether it existed
+pub fn remove(client: &mut Connection, id: u64) -> RedisResult<bool> {
+    let _: () = client.del(format!("agenda_reminders:{}", id))?;
+    client.hdel("agenda", id)
+}
+
+// Get every item ordered by when it starts
+pub fn all(client: &mut Connection) -> RedisResult<Vec<Item>> {
+    let raw: Vec<String> = client.hvals("agenda")?;
+    let mut items = raw
+        .iter()
+        .filter_map(|raw| serde_json::from_str::<Item>(raw).ok())
+        .collect::<Vec<_>>();
+    items.sort_by_key(|i| (i.start, i.id));
+    Ok(items)
+}
+
+// Toggle whether a user is sent a reminder for an item, returning whether they now are
+pub fn toggle_reminder(client: &mut Connection, id: u64, user: u64) -> RedisResult<bool> {
+    let key = format!("agenda_reminders:{}", id);
+    let removed: bool = client.srem(&key, user)?;
+    if !removed {
+        let _: () = client.sadd(&key, user)?;
+    }
+    Ok(!removed)
+}
+
+// Get the users that want a reminder for an item
+pub fn reminders(client: &mut Connection, id: u64) -> RedisResult<Vec<u64>> {
+    client.smembers(format!("agenda_reminders:{}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2020-10-24 09:00 in Los Angeles
+    const MORNING: i64 = 1_603_555_200_000;
+
+    fn timezone() -> Tz {
+        "America/Los_Angeles".parse().unwrap()
+    }
+
+    #[test]
+    fn parse_reads_items_in_the_event_timezone() {
+        let items = parse(
+            "Title,Start,End,Location,Description\n\
+             Opening, 2020-10-24 09:00 ,2020-10-24 10:30,Main stage,\"Welcome, everyone\"\n\
+             \n\
+             Lunch,in 1h,in 2h,,\n",
+            timezone(),
+            MORNING,
+        )
+        .unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Opening");
+        assert_eq!(items[0].start, MORNING);
+        assert_eq!(items[0].end, MORNING + 90 * 60_000);
+        assert_eq!(items[0].location, "Main stage");
+        assert_eq!(items[0].description, "Welcome, everyone");
+        assert_eq!(items[1].start, MORNING + 3_600_000);
+        assert_eq!(items[1].location, "");
+    }
+
+    #[test]
+    fn parse_requires_items_to_start_before_they_end() {
+        for line in &[
+            "Opening,2020-10-24 10:30,2020-10-24 09:00,,",
+            "Opening,2020-10-24 09:00,2020-10-24 09:00,,",
+            "Opening,tomorrow,2020-10-24 09:00,,",
+        ] {
+            let why = parse(line, timezone(), MORNING).err().unwrap();
+            assert!(why.starts_with("Line 1 must have a start before its end"));
+        }
+    }
+
+    #[test]
+    fn parse_reports_missing_columns() {
+        let why = parse(
+            "Opening,2020-10-24 09:00,2020-10-24 10:00,,\nLunch,in 1h,in 2h",
+            timezone(),
+            MORNING,
+        )
+        .err()
+        .unwrap();
+        assert!(why.starts_with("Line 2 has 3 columns"));
+    }
+}
@@ -0,0 +1,131 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+/// When an announcement is posted
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Timing {
+    /// Once at a time in milliseconds
+    Once { at: i64 },
+    /// Repeatedly starting some time before the end of the event
+    Countdown { every: i64, starting: i64 },
+}
+
+/// A message posted to a channel at a later time
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: u64,
+    pub channel: u64,
+    pub message: String,
+    pub timing: Timing,
+}
+
+impl Announcement {
+    /// Get the next time to post the announcement after a time, if there is one
+    pub fn next(&self, after: i64, end: Option<i64>) -> Option<i64> {
+        match self.timing {
+            Timing::Once { at } if at > after => Some(at),
+            Timing::Once { .. } => None,
+            Timing::Countdown { every, starting } => {
+                let end = end?;
+                let first = end - starting;
+                let next = if after < first {
+                    first
+                } else {
+                    first + ((after - first) / every + 1) * every
+                };
+                if next < end {
+                    Some(next)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+// Persist a new announcement, assigning it an id
+pub fn create(
+    client: &mut Connection,
+    channel: u64,
+    message: String,
+    timing: Timing,
+) -> RedisResult<Announcement> {
+    let id: u64 = client.incr("announcement_id", 1)?;
+    let announcement = Announcement {
+        id,
+        channel,
+        message,
+        timing,
+    };
+    let raw = serde_json::to_string(&announcement).expect("Failed to serialize announcement");
+    let _: () = client.hset("announcements", id, raw)?;
+    Ok(announcement)
+}
+
+// Get an announcement by its id
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<Announcement>> {
+    let raw: Option<String> = client.hget("announcements", id)?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Delete an announcement, returning whether it existed
+pub fn remove(client: &mut Connection, id: u64) -> RedisResult<bool> {
+    client.hdel("announcements", id)
+}
+
+// Get all the announcements ordered by id
+pub fn all(client: &mut Connection) -> RedisResult<Vec<Announcement>> {
+    let raw: Vec<String> = client.hvals("announcements")?;
+    let mut announcements = raw
+        .iter()
+        .filter_map(|raw| serde_json::from_str::<Announcement>(raw).ok())
+        .collect::<Vec<_>>();
+    announcements.sort_by_key(|a| a.id);
+    Ok(announcements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn announcement(timing: Timing) -> Announcement {
+        Announcement {
+            id: 1,
+            channel: 1,
+            message: String::new(),
+            timing,
+        }
+    }
+
+    #[test]
+    fn once_is_posted_only_before_its_time() {
+        let once = announcement(Timing::Once { at: 1_000 });
+        assert_eq!(once.next(0, None), Some(1_000));
+        assert_eq!(once.next(999, Some(500)), Some(1_000));
+        assert_eq!(once.next(1_000, None), None);
+    }
+
+    #[test]
+    fn countdown_repeats_until_the_end() {
+        // Every 10 starting 30 before an end at 100
+        let countdown = announcement(Timing::Countdown {
+            every: 10,
+            starting: 30,
+        });
+        assert_eq!(countdown.next(0, Some(100)), Some(70));
+        assert_eq!(countdown.next(70, Some(100)), Some(80));
+        assert_eq!(countdown.next(75, Some(100)), Some(80));
+        assert_eq!(countdown.next(89, Some(100)), Some(90));
+        assert_eq!(countdown.next(90, Some(100)), None);
+    }
+
+    #[test]
+    fn countdown_needs_an_end() {
+        let countdown = announcement(Timing::Countdown {
+            every: 10,
+            starting: 30,
+        });
+        assert_eq!(countdown.next(0, None), None);
+    }
+}
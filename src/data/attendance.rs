@@ -0,0 +1,45 @@
+use redis::{Commands, Connection, RedisResult};
+use std::collections::HashMap;
+
+// Open a check-in window for a number of seconds, closing any other one
+pub fn open(client: &mut Connection, window: &str, seconds: usize, at: i64) -> RedisResult<()> {
+    let _: () = client.set_ex("checkin_window", window, seconds)?;
+    // Keep when the window was first opened so reopening it doesn't reorder the report
+    redis::cmd("ZADD")
+        .arg("checkin_windows")
+        .arg("NX")
+        .arg(at)
+        .arg(window)
+        .query(client)
+}
+
+// Close the current check-in window early
+pub fn close(client: &mut Connection) -> RedisResult<Option<String>> {
+    let window: Option<String> = client.get("checkin_window")?;
+    let _: () = client.del("checkin_window")?;
+    Ok(window)
+}
+
+// Get the current check-in window and how many seconds are left in it
+pub fn current(client: &mut Connection) -> RedisResult<Option<(String, i64)>> {
+    let window: Option<String> = client.get("checkin_window")?;
+    match window {
+        Some(window) => Ok(Some((window, client.ttl("checkin_window")?))),
+        None => Ok(None),
+    }
+}
+
+// Get every window that has been opened in the order they were first opened
+pub fn windows(client: &mut Connection) -> RedisResult<Vec<String>> {
+    client.zrange("checkin_windows", 0, -1)
+}
+
+// Record that a user checked in, returning whether they hadn't already for this window
+pub fn check_in(client: &mut Connection, window: &str, user: u64, at: i64) -> RedisResult<bool> {
+    client.hset_nx(format!("attendance:{}", window), user, at)
+}
+
+// Get when every user checked in during a window
+pub fn attendees(client: &mut Connection, window: &str) -> RedisResult<HashMap<u64, i64>> {
+    client.hgetall(format!("attendance:{}", window))
+}
@@ -0,0 +1,46 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+// How long message content is kept for logging edits and deletions
+const CACHE_TTL: usize = 24 * 60 * 60;
+
+/// The last known content of a message
+#[derive(Serialize, Deserialize)]
+pub struct CachedMessage {
+    pub author: u64,
+    pub author_tag: String,
+    pub channel: u64,
+    pub content: String,
+    pub attachments: Vec<String>,
+}
+
+// Remember the content of a message for a limited time
+pub fn cache(client: &mut Connection, id: u64, message: &CachedMessage) -> RedisResult<()> {
+    let raw = serde_json::to_string(message).expect("Failed to serialize message");
+    client.set_ex(format!("audit_message:{}", id), raw, CACHE_TTL)
+}
+
+// Retrieve the content of a message, if it is still known
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<CachedMessage>> {
+    let raw: Option<String> = client.get(format!("audit_message:{}", id))?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Forget the content of a message
+pub fn remove(client: &mut Connection, id: u64) -> RedisResult<()> {
+    client.del(format!("audit_message:{}", id))
+}
+
+// Set whether messages in the team channels are logged
+pub fn set_log_tables(client: &mut Connection, enabled: bool) -> RedisResult<()> {
+    if enabled {
+        client.set("audit_log_tables", 1)
+    } else {
+        client.del("audit_log_tables")
+    }
+}
+
+// Check whether messages in the team channels are logged
+pub fn log_tables(client: &mut Connection) -> RedisResult<bool> {
+    client.exists("audit_log_tables")
+}
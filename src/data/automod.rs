@@ -0,0 +1,131 @@
+use redis::{Commands, Connection, RedisResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// What a rule looks for in a message
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    Word,
+    Regex,
+    Invites,
+    Mentions,
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RuleKind::Word => "word",
+            RuleKind::Regex => "regex",
+            RuleKind::Invites => "invites",
+            RuleKind::Mentions => "mentions",
+        })
+    }
+}
+
+impl FromStr for RuleKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "word" => Ok(RuleKind::Word),
+            "regex" => Ok(RuleKind::Regex),
+            "invites" | "invite" => Ok(RuleKind::Invites),
+            "mentions" | "mention" => Ok(RuleKind::Mentions),
+            _ => Err(format!(
+                "'{}' is not one of word, regex, invites, or mentions",
+                s
+            )),
+        }
+    }
+}
+
+/// What happens to a message that matches a rule
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Delete,
+    Warn,
+    Case,
+    Ping,
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RuleAction::Delete => "delete",
+            RuleAction::Warn => "warn",
+            RuleAction::Case => "case",
+            RuleAction::Ping => "ping",
+        })
+    }
+}
+
+impl FromStr for RuleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delete" => Ok(RuleAction::Delete),
+            "warn" => Ok(RuleAction::Warn),
+            "case" => Ok(RuleAction::Case),
+            "ping" => Ok(RuleAction::Ping),
+            _ => Err(format!("'{}' is not one of delete, warn, case, or ping", s)),
+        }
+    }
+}
+
+/// A filter applied to every message
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: u64,
+    pub kind: RuleKind,
+    pub action: RuleAction,
+    /// The word or expression to match, or the number of mentions allowed
+    pub pattern: String,
+}
+
+lazy_static! {
+    // Matches Discord invite links
+    static ref INVITE_PATTERN: Regex =
+        Regex::new(r"(?i)(discord\.gg|discord(app)?\.com/invite)/[a-z0-9-]+").unwrap();
+}
+
+impl Rule {
+    /// Compile the expression the rule matches message content with
+    pub fn regex(&self) -> Result<Option<Regex>, regex::Error> {
+        match self.kind {
+            RuleKind::Word => {
+                Regex::new(&format!(r"(?i)\b{}\b", regex::escape(&self.pattern))).map(Some)
+            }
+            RuleKind::Regex => Regex::new(&self.pattern).map(Some),
+            RuleKind::Invites => Ok(Some(INVITE_PATTERN.clone())),
+            RuleKind::Mentions => Ok(None),
+        }
+    }
+}
+
+// Persist a new rule, assigning it the next id
+pub fn add(client: &mut Connection, mut rule: Rule) -> RedisResult<Rule> {
+    rule.id = client.incr("automod_rule_id", 1)?;
+    let raw = serde_json::to_string(&rule).expect("Failed to serialize rule");
+    let _: () = client.hset("automod_rules", rule.id, raw)?;
+    Ok(rule)
+}
+
+// Remove a rule, returning whether it existed
+pub fn remove(client: &mut Connection, id: u64) -> RedisResult<bool> {
+    client.hdel("automod_rules", id)
+}
+
+// Retrieve all the rules, oldest first
+pub fn all(client: &mut Connection) -> RedisResult<Vec<Rule>> {
+    let raw: Vec<String> = client.hvals("automod_rules")?;
+    let mut rules = raw
+        .iter()
+        .filter_map(|r| serde_json::from_str::<Rule>(r).ok())
+        .collect::<Vec<_>>();
+    rules.sort_by_key(|r| r.id);
+    Ok(rules)
+}
@@ -0,0 +1,310 @@
+use chrono::prelude::*;
+use redis::{Commands, Connection, ConnectionLike, ErrorKind, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The current version of the snapshot format
+pub const VERSION: u32 = 1;
+
+/// A point-in-time copy of all the bot's state
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub created_at: i64,
+    pub keys: BTreeMap<String, Entry>,
+}
+
+/// A single key and its remaining time to live
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    pub value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<usize>,
+}
+
+/// The contents of a key based on its type
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Value {
+    String(String),
+    List(Vec<String>),
+    Set(Vec<String>),
+    Hash(BTreeMap<String, String>),
+    Zset(Vec<(String, f64)>),
+}
+
+// Create a snapshot of every key in the database
+pub fn export(client: &mut Connection) -> RedisResult<Snapshot> {
+    let names: Vec<String> = client.scan::<String>()?.collect();
+
+    let mut keys = BTreeMap::new();
+    for name in names {
+        let kind: String = redis::cmd("TYPE").arg(&name).query(client)?;
+        let value = match kind.as_str() {
+            "string" => Value::String(client.get(&name)?),
+            "list" => Value::List(client.lrange(&name, 0, -1)?),
+            "set" => Value::Set(client.smembers(&name)?),
+            "hash" => Value::Hash(client.hgetall(&name)?),
+            "zset" => Value::Zset(client.zrange_withscores(&name, 0, -1)?),
+            // The key expired since it was scanned
+            "none" => continue,
+            _ => {
+                return Err(RedisError::from((
+                    ErrorKind::TypeError,
+                    "Unsupported key type",
+                    format!("'{}' has type '{}'", name, kind),
+                )))
+            }
+        };
+
+        let ttl: i64 = client.ttl(&name)?;
+        let ttl = if ttl > 0 { Some(ttl as usize) } else { None };
+
+        keys.insert(name, Entry { value, ttl });
+    }
+
+    Ok(Snapshot {
+        version: VERSION,
+        created_at: Utc::now().timestamp_millis(),
+        keys,
+    })
+}
+
+// Keys the bot writes on its own when it starts or while it runs, which don't
+// count as existing data
+const BOT_OWNED: &[&str] = &["config", "schema_version"];
+const BOT_OWNED_PREFIXES: &[&str] = &["ratelimit:", "ratelimit_strikes:", "audit_message:"];
+
+fn is_bot_owned(name: &str) -> bool {
+    BOT_OWNED.contains(&name) || BOT_OWNED_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+// Check whether the database has no keys besides the ones the bot writes on its own
+pub fn is_empty(client: &mut Connection) -> RedisResult<bool> {
+    let mut names = client.scan::<String>()?;
+    Ok(!names.any(|name| !is_bot_owned(&name)))
+}
+
+// Restore a snapshot into the database, returning the number of keys written
+pub fn import(client: &mut dyn ConnectionLike, snapshot: &Snapshot) -> RedisResult<usize> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    // Snapshots from before the schema was versioned need every migration, which only
+    // run once the version the bot wrote on startup is gone
+    if !snapshot.keys.contains_key("schema_version") {
+        pipe.del("schema_version").ignore();
+    }
+
+    for (name, entry) in &snapshot.keys {
+        // Replace keys the bot already wrote instead of merging into them
+        pipe.del(name).ignore();
+        match &entry.value {
+            Value::String(value) => pipe.set(name, value).ignore(),
+            Value::List(values) => pipe.rpush(name, &values[..]).ignore(),
+            Value::Set(values) => pipe.sadd(name, &values[..]).ignore(),
+            Value::Hash(values) => pipe
+                .hset_multiple(name, &values.iter().collect::<Vec<_>>())
+                .ignore(),
+            Value::Zset(values) => pipe
+                .zadd_multiple(
+                    name,
+                    &values.iter().map(|(m, s)| (*s, m)).collect::<Vec<_>>(),
+                )
+                .ignore(),
+        };
+
+        if let Some(ttl) = entry.ttl {
+            pipe.expire(name, ttl).ignore();
+        }
+    }
+
+    pipe.query::<()>(client)?;
+    Ok(snapshot.keys.len())
+}
+
+// Parse a snapshot, ensuring it is a supported version
+pub fn parse(raw: &[u8]) -> Result<Snapshot, String> {
+    let snapshot: Snapshot =
+        serde_json::from_slice(raw).map_err(|e| format!("Invalid snapshot: {}", e))?;
+
+    if snapshot.version != VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {}, expected {}",
+            snapshot.version, VERSION
+        ));
+    }
+
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            "announcement_counter".to_string(),
+            Entry {
+                value: Value::String("3".to_string()),
+                ttl: None,
+            },
+        );
+        keys.insert(
+            "queue".to_string(),
+            Entry {
+                value: Value::List(vec!["1".to_string(), "2".to_string()]),
+                ttl: Some(60),
+            },
+        );
+        keys.insert(
+            "tracks".to_string(),
+            Entry {
+                value: Value::Set(vec!["Hardware".to_string()]),
+                ttl: None,
+            },
+        );
+        let mut hash = BTreeMap::new();
+        hash.insert("mentors_channel".to_string(), "42".to_string());
+        keys.insert(
+            "config".to_string(),
+            Entry {
+                value: Value::Hash(hash),
+                ttl: None,
+            },
+        );
+        keys.insert(
+            "announcements".to_string(),
+            Entry {
+                value: Value::Zset(vec![("1".to_string(), 1.5e12)]),
+                ttl: Some(3600),
+            },
+        );
+
+        Snapshot {
+            version: VERSION,
+            created_at: 1_600_000_000_000,
+            keys,
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let snapshot = snapshot();
+        let raw = serde_json::to_vec(&snapshot).unwrap();
+        assert_eq!(parse(&raw).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn entries_flatten_their_type_and_value() {
+        let raw = serde_json::to_value(snapshot()).unwrap();
+        assert_eq!(
+            raw["keys"]["queue"],
+            serde_json::json!({ "type": "list", "value": ["1", "2"], "ttl": 60 })
+        );
+        assert_eq!(
+            raw["keys"]["config"],
+            serde_json::json!({ "type": "hash", "value": { "mentors_channel": "42" } })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_other_versions() {
+        let mut snapshot = snapshot();
+        snapshot.version = VERSION + 1;
+        let raw = serde_json::to_vec(&snapshot).unwrap();
+        assert!(parse(&raw).is_err());
+        assert!(parse(b"{}").is_err());
+    }
+
+    // Records the commands sent instead of running them
+    struct Recorder(Vec<u8>);
+
+    impl ConnectionLike for Recorder {
+        fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<redis::Value> {
+            self.0.extend_from_slice(cmd);
+            Ok(redis::Value::Okay)
+        }
+
+        fn req_packed_commands(
+            &mut self,
+            cmd: &[u8],
+            _: usize,
+            _: usize,
+        ) -> RedisResult<Vec<redis::Value>> {
+            self.0.extend_from_slice(cmd);
+            Ok(vec![redis::Value::Bulk(Vec::new())])
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+
+        fn check_connection(&mut self) -> bool {
+            true
+        }
+
+        fn is_open(&self) -> bool {
+            true
+        }
+    }
+
+    impl Recorder {
+        fn sent(&self, command: &str, key: &str) -> bool {
+            let packed = format!("{}\r\n${}\r\n{}\r\n", command, key.len(), key);
+            self.0.windows(packed.len()).any(|w| w == packed.as_bytes())
+        }
+    }
+
+    #[test]
+    fn import_clears_the_schema_version_for_unversioned_snapshots() {
+        // Exported before the schema was versioned, with help requests still as lists
+        let mut snapshot = snapshot();
+        snapshot.keys.insert(
+            "help_request:abc".to_string(),
+            Entry {
+                value: Value::List(vec![
+                    "description".to_string(),
+                    "link".to_string(),
+                    "Table 1".to_string(),
+                    "0".to_string(),
+                ]),
+                ttl: None,
+            },
+        );
+
+        let mut recorder = Recorder(Vec::new());
+        assert_eq!(import(&mut recorder, &snapshot).unwrap(), 6);
+        assert!(recorder.sent("DEL", "schema_version"));
+        assert!(!recorder.sent("SET", "schema_version"));
+        assert!(recorder.sent("RPUSH", "help_request:abc"));
+    }
+
+    #[test]
+    fn import_restores_the_schema_version_of_versioned_snapshots() {
+        let mut snapshot = snapshot();
+        snapshot.keys.insert(
+            "schema_version".to_string(),
+            Entry {
+                value: Value::String("1".to_string()),
+                ttl: None,
+            },
+        );
+
+        let mut recorder = Recorder(Vec::new());
+        import(&mut recorder, &snapshot).unwrap();
+        assert!(recorder.sent("SET", "schema_version"));
+    }
+
+    #[test]
+    fn bot_owned_keys_are_ignored() {
+        assert!(is_bot_owned("config"));
+        assert!(is_bot_owned("schema_version"));
+        assert!(is_bot_owned("ratelimit:command:import:1"));
+        assert!(is_bot_owned("audit_message:1"));
+        assert!(!is_bot_owned("roster"));
+        assert!(!is_bot_owned("configs"));
+    }
+}
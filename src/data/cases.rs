@@ -0,0 +1,199 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// The stages a case moves through
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Open => "open",
+            Status::Acknowledged => "acknowledged",
+            Status::Resolved => "resolved",
+        })
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "open" => Ok(Status::Open),
+            "acknowledged" | "ack" => Ok(Status::Acknowledged),
+            "resolved" => Ok(Status::Resolved),
+            _ => Err(format!(
+                "'{}' is not one of open, acknowledged, or resolved",
+                s
+            )),
+        }
+    }
+}
+
+/// A copy of a reported message that survives its deletion
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Evidence {
+    pub author: u64,
+    pub author_tag: String,
+    pub channel: u64,
+    pub message: u64,
+    pub content: String,
+    pub attachments: Vec<String>,
+    pub created_at: i64,
+    pub edited_at: Option<i64>,
+}
+
+/// A message exchanged between the reporter and the moderators
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub author: u64,
+    pub from_reporter: bool,
+    pub content: String,
+    pub at: i64,
+}
+
+/// A report tracked by the moderators
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Case {
+    pub id: u64,
+    pub kind: String,
+    pub status: Status,
+    /// The user that reported it, unless the bot opened it on its own
+    #[serde(default)]
+    pub reporter: Option<u64>,
+    pub channel: u64,
+    pub message: String,
+    #[serde(default)]
+    pub assignee: Option<u64>,
+    #[serde(default)]
+    pub resolution: Option<String>,
+    #[serde(default)]
+    pub evidence: Option<Evidence>,
+    #[serde(default)]
+    pub alert_message: Option<u64>,
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    /// Ids of the moderation actions taken for the case
+    #[serde(default)]
+    pub actions: Vec<u64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Case {
+    /// Create a new open case
+    pub fn new(kind: &str, reporter: Option<u64>, channel: u64, message: String, at: i64) -> Self {
+        Case {
+            id: 0,
+            kind: kind.to_string(),
+            status: Status::Open,
+            reporter,
+            channel,
+            message,
+            assignee: None,
+            resolution: None,
+            evidence: None,
+            alert_message: None,
+            anonymous: false,
+            notes: Vec::new(),
+            actions: Vec::new(),
+            created_at: at,
+            updated_at: at,
+        }
+    }
+
+    /// How the reporter should be shown to the moderators
+    pub fn reporter_display(&self) -> String {
+        match self.reporter {
+            _ if self.anonymous => "An anonymous user".to_string(),
+            Some(reporter) => format!("<@{}>", reporter),
+            None => "The bot".to_string(),
+        }
+    }
+}
+
+// Persist a new case, assigning it the next id
+pub fn create(client: &mut Connection, mut case: Case) -> RedisResult<Case> {
+    case.id = client.incr("case_id", 1)?;
+    save(client, &case)?;
+    let _: () = client.zadd("cases", case.id, case.id)?;
+    Ok(case)
+}
+
+// Persist the changes to an existing case
+pub fn save(client: &mut Connection, case: &Case) -> RedisResult<()> {
+    let raw = serde_json::to_string(case).expect("Failed to serialize case");
+    client.set(format!("case:{}", case.id), raw)
+}
+
+// Retrieve a case by its id
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<Case>> {
+    let raw: Option<String> = client.get(format!("case:{}", id))?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Retrieve all the cases, oldest first
+pub fn all(client: &mut Connection) -> RedisResult<Vec<Case>> {
+    let ids: Vec<u64> = client.zrange("cases", 0, -1)?;
+
+    let mut cases = Vec::new();
+    for id in ids {
+        if let Some(case) = get(client, id)? {
+            cases.push(case);
+        }
+    }
+
+    Ok(cases)
+}
+
+// Link the message alerting the moderators to a case
+pub fn set_alert_message(
+    client: &mut Connection,
+    case: &mut Case,
+    message: u64,
+) -> RedisResult<()> {
+    case.alert_message = Some(message);
+    save(client, case)?;
+    client.set(format!("case_alert:{}", message), case.id)
+}
+
+// Find the case a message alerting the moderators belongs to
+pub fn find_by_alert_message(client: &mut Connection, message: u64) -> RedisResult<Option<Case>> {
+    let id: Option<u64> = client.get(format!("case_alert:{}", message))?;
+    match id {
+        Some(id) => get(client, id),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reporter_is_optional() {
+        let case = Case::new("automod", None, 1, "spam".to_string(), 0);
+        let raw = serde_json::to_string(&case).unwrap();
+        let case: Case = serde_json::from_str(&raw).unwrap();
+        assert_eq!(case.reporter, None);
+        assert_eq!(case.reporter_display(), "The bot");
+
+        // Cases saved before the reporter was optional
+        let raw = raw.replace("\"reporter\":null", "\"reporter\":42");
+        let mut case: Case = serde_json::from_str(&raw).unwrap();
+        assert_eq!(case.reporter, Some(42));
+        assert_eq!(case.reporter_display(), "<@42>");
+
+        case.anonymous = true;
+        assert_eq!(case.reporter_display(), "An anonymous user");
+    }
+}
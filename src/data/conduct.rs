@@ -0,0 +1,35 @@
+use redis::{Commands, Connection, RedisResult};
+use std::collections::HashMap;
+
+// Remember the message members react to in order to accept the code of conduct
+pub fn set_message(client: &mut Connection, message: u64) -> RedisResult<()> {
+    client.set("conduct_message", message)
+}
+
+// Get the message members react to, which is only set once the code of conduct is posted
+pub fn message(client: &mut Connection) -> RedisResult<Option<u64>> {
+    client.get("conduct_message")
+}
+
+// Record that a user accepted the code of conduct, returning whether they hadn't already
+pub fn accept(client: &mut Connection, user: u64, at: i64) -> RedisResult<bool> {
+    client.hset_nx("conduct_acceptances", user, at)
+}
+
+// Get when a user accepted the code of conduct
+pub fn accepted_at(client: &mut Connection, user: u64) -> RedisResult<Option<i64>> {
+    client.hget("conduct_acceptances", user)
+}
+
+// Get when every user accepted the code of conduct
+pub fn all(client: &mut Connection) -> RedisResult<HashMap<u64, i64>> {
+    client.hgetall("conduct_acceptances")
+}
+
+// Check whether a user may participate, which is always the case until the code of conduct is posted
+pub fn has_accepted(client: &mut Connection, user: u64) -> RedisResult<bool> {
+    if message(client)?.is_none() {
+        return Ok(true);
+    }
+    Ok(accepted_at(client, user)?.is_some())
+}
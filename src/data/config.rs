@@ -0,0 +1,389 @@
+use redis::{Commands, Connection, ErrorKind, RedisError, RedisResult};
+use serenity::{
+    model::{
+        channel::ChannelType,
+        channel::{PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId, RoleId},
+        permissions::Permissions,
+    },
+    prelude::Context,
+};
+use std::collections::HashMap;
+
+use crate::util::parse_from_environment;
+
+/// What muted, quarantined, and unverified members are denied in every channel
+pub const SILENCED: Permissions = Permissions::from_bits_truncate(
+    Permissions::SEND_MESSAGES.bits()
+        | Permissions::ADD_REACTIONS.bits()
+        | Permissions::SPEAK.bits(),
+);
+
+/// Settings for the roles that are denied `SILENCED`
+pub const SILENCED_ROLES: &[&str] = &["muted_role", "quarantine_role", "unverified_role"];
+
+/// The different types of Discord resources a setting can refer to
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Category,
+    TextChannel,
+    Role,
+    AssignableRole,
+}
+
+impl Kind {
+    /// Human readable name of the resource
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Category => "category",
+            Kind::TextChannel => "text channel",
+            Kind::Role | Kind::AssignableRole => "role",
+        }
+    }
+}
+
+/// A runtime configurable id
+pub struct Setting {
+    pub name: &'static str,
+    pub variable: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+    /// Whether the bot can't run without it, otherwise only its feature is turned off
+    pub required: bool,
+}
+
+/// All the ids that can be configured at runtime
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        name: "tables_category",
+        variable: "TABLES_CATEGORY_ID",
+        kind: Kind::Category,
+        description: "Category where the team channels are created",
+        required: true,
+    },
+    Setting {
+        name: "reports_channel",
+        variable: "REPORTS_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where reports are sent",
+        required: true,
+    },
+    Setting {
+        name: "mentors_channel",
+        variable: "MENTORS_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where mentor notifications are sent",
+        required: true,
+    },
+    Setting {
+        name: "modlog_channel",
+        variable: "MODLOG_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where moderation actions are logged",
+        required: false,
+    },
+    Setting {
+        name: "audit_channel",
+        variable: "AUDIT_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where edited and deleted messages are logged",
+        required: false,
+    },
+    Setting {
+        name: "gate_channel",
+        variable: "GATE_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where the code of conduct is posted",
+        required: false,
+    },
+    Setting {
+        name: "announcements_channel",
+        variable: "ANNOUNCEMENTS_CHANNEL_ID",
+        kind: Kind::TextChannel,
+        description: "Channel where event announcements are posted",
+        required: false,
+    },
+    Setting {
+        name: "everyone_role",
+        variable: "EVERYONE_ROLE_ID",
+        kind: Kind::Role,
+        description: "The @everyone role",
+        required: true,
+    },
+    Setting {
+        name: "bot_role",
+        variable: "BOT_ROLE_ID",
+        kind: Kind::Role,
+        description: "The role for this bot",
+        required: true,
+    },
+    Setting {
+        name: "teamless_role",
+        variable: "TEAMLESS_ROLE_ID",
+        kind: Kind::AssignableRole,
+        description: "Role for all users without a team",
+        required: true,
+    },
+    Setting {
+        name: "mentor_role",
+        variable: "MENTOR_ROLE_ID",
+        kind: Kind::Role,
+        description: "Role for all mentors",
+        required: true,
+    },
+    Setting {
+        name: "judge_role",
+        variable: "JUDGE_ROLE_ID",
+        kind: Kind::Role,
+        description: "Role for all judges",
+        required: false,
+    },
+    Setting {
+        name: "manager_role",
+        variable: "MANAGER_ROLE_ID",
+        kind: Kind::Role,
+        description: "Role for all managers/administrators",
+        required: true,
+    },
+    Setting {
+        name: "muted_role",
+        variable: "MUTED_ROLE_ID",
+        kind: Kind::AssignableRole,
+        description: "Role for users that are muted",
+        required: false,
+    },
+    Setting {
+        name: "quarantine_role",
+        variable: "QUARANTINE_ROLE_ID",
+        kind: Kind::AssignableRole,
+        description: "Role for users that joined during a lockdown",
+        required: false,
+    },
+    Setting {
+        name: "unverified_role",
+        variable: "UNVERIFIED_ROLE_ID",
+        kind: Kind::AssignableRole,
+        description: "Role for users that haven't verified their registration",
+        required: false,
+    },
+];
+
+/// The current configuration of the bot, where features that aren't configured are `None`
+#[derive(Clone)]
+pub struct Config {
+    pub tables_category: ChannelId,
+    pub reports_channel: ChannelId,
+    pub mentors_channel: ChannelId,
+    pub modlog_channel: Option<ChannelId>,
+    pub audit_channel: Option<ChannelId>,
+    pub gate_channel: Option<ChannelId>,
+    pub announcements_channel: Option<ChannelId>,
+    pub everyone_role: RoleId,
+    pub bot_role: RoleId,
+    pub teamless_role: RoleId,
+    pub mentor_role: RoleId,
+    pub judge_role: Option<RoleId>,
+    pub manager_role: RoleId,
+    pub muted_role: Option<RoleId>,
+    pub quarantine_role: Option<RoleId>,
+    pub unverified_role: Option<RoleId>,
+}
+
+impl Config {
+    /// Overwrites denying the configured silenced roles from talking, for new channels
+    pub fn silenced(&self) -> Vec<PermissionOverwrite> {
+        [self.muted_role, self.quarantine_role, self.unverified_role]
+            .iter()
+            .flatten()
+            .map(|role| PermissionOverwrite {
+                kind: PermissionOverwriteType::Role(*role),
+                allow: Permissions::empty(),
+                deny: SILENCED,
+            })
+            .collect()
+    }
+}
+
+// Find a setting by its name
+pub fn find(name: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.name == name)
+}
+
+// Get the value of a single setting
+pub fn get(client: &mut Connection, name: &str) -> RedisResult<Option<u64>> {
+    client.hget("config", name)
+}
+
+// Persist the value of a single setting
+pub fn set(client: &mut Connection, name: &str, value: u64) -> RedisResult<()> {
+    client.hset("config", name, value)
+}
+
+// Get the values of all the settings that are set
+pub fn all(client: &mut Connection) -> RedisResult<HashMap<String, u64>> {
+    client.hgetall("config")
+}
+
+// Load the full configuration, failing if any required setting is missing
+pub fn load(client: &mut Connection) -> RedisResult<Config> {
+    let values = all(client)?;
+    let value = |name: &'static str| match values.get(name) {
+        Some(value) => Ok(*value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Configuration value is not set",
+            name.to_string(),
+        ))),
+    };
+
+    Ok(Config {
+        tables_category: ChannelId(value("tables_category")?),
+        reports_channel: ChannelId(value("reports_channel")?),
+        mentors_channel: ChannelId(value("mentors_channel")?),
+        modlog_channel: values.get("modlog_channel").map(|id| ChannelId(*id)),
+        audit_channel: values.get("audit_channel").map(|id| ChannelId(*id)),
+        gate_channel: values.get("gate_channel").map(|id| ChannelId(*id)),
+        announcements_channel: values.get("announcements_channel").map(|id| ChannelId(*id)),
+        everyone_role: RoleId(value("everyone_role")?),
+        bot_role: RoleId(value("bot_role")?),
+        teamless_role: RoleId(value("teamless_role")?),
+        mentor_role: RoleId(value("mentor_role")?),
+        judge_role: values.get("judge_role").map(|id| RoleId(*id)),
+        manager_role: RoleId(value("manager_role")?),
+        muted_role: values.get("muted_role").map(|id| RoleId(*id)),
+        quarantine_role: values.get("quarantine_role").map(|id| RoleId(*id)),
+        unverified_role: values.get("unverified_role").map(|id| RoleId(*id)),
+    })
+}
+
+// Seed any unset values from their environment variables
+pub fn seed(client: &mut Connection) -> RedisResult<()> {
+    for setting in SETTINGS {
+        // Invalid values are reported by `check_environment`
+        if let Ok(value) = parse_from_environment::<u64>(setting.variable) {
+            let _: () = client.hset_nx("config", setting.name, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Check that an id refers to a usable resource of the correct type in the guild
+pub fn validate(
+    ctx: &Context,
+    guild_id: GuildId,
+    setting: &Setting,
+    id: u64,
+) -> Result<(), String> {
+    let guild = match guild_id.to_guild_cached(&ctx.cache) {
+        Some(guild) => guild,
+        None => return Err("The server is not available".to_string()),
+    };
+    let guild = guild.read();
+    let bot_id = ctx.cache.read().user.id;
+
+    match setting.kind {
+        Kind::Category | Kind::TextChannel => {
+            let channel = match guild.channels.get(&ChannelId(id)) {
+                Some(channel) => channel.read(),
+                None => return Err(format!("No {} with id {} exists", setting.kind.name(), id)),
+            };
+
+            let expected = match setting.kind {
+                Kind::Category => ChannelType::Category,
+                _ => ChannelType::Text,
+            };
+            if channel.kind != expected {
+                return Err(format!(
+                    "#{} is not a {}",
+                    channel.name,
+                    setting.kind.name()
+                ));
+            }
+
+            let required = match setting.kind {
+                Kind::Category => Permissions::READ_MESSAGES | Permissions::MANAGE_CHANNELS,
+                _ => Permissions::READ_MESSAGES | Permissions::SEND_MESSAGES,
+            };
+            if !guild
+                .user_permissions_in(channel.id, bot_id)
+                .contains(required)
+            {
+                return Err(format!(
+                    "The bot is missing permissions in #{}, it requires: {:?}",
+                    channel.name, required
+                ));
+            }
+        }
+        Kind::Role | Kind::AssignableRole => {
+            let role = match guild.roles.get(&RoleId(id)) {
+                Some(role) => role,
+                None => return Err(format!("No role with id {} exists", id)),
+            };
+
+            if setting.name == "everyone_role" && role.id.0 != guild.id.0 {
+                return Err(format!("@{} is not the @everyone role", role.name));
+            }
+
+            if setting.kind == Kind::AssignableRole {
+                let highest = guild
+                    .members
+                    .get(&bot_id)
+                    .map(|m| {
+                        m.roles
+                            .iter()
+                            .filter_map(|r| guild.roles.get(r))
+                            .map(|r| r.position)
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .unwrap_or(0);
+
+                if role.position >= highest {
+                    return Err(format!(
+                        "@{} must be below the bot's highest role to be assigned",
+                        role.name
+                    ));
+                }
+                if !guild
+                    .member_permissions(bot_id)
+                    .contains(Permissions::MANAGE_ROLES)
+                {
+                    return Err("The bot is missing the 'Manage Roles' permission".to_string());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Check every setting against the guild, returning all the problems found
+pub fn verify(ctx: &Context, guild_id: GuildId, values: &HashMap<String, u64>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for setting in SETTINGS {
+        match values.get(setting.name) {
+            Some(id) => {
+                if let Err(why) = validate(ctx, guild_id, setting, *id) {
+                    problems.push(format!("`{}`: {}", setting.name, why));
+                }
+            }
+            None if setting.required => problems.push(format!("`{}`: not set", setting.name)),
+            None => {}
+        }
+    }
+
+    // Ensure the bot has the server-wide permissions it needs
+    let required =
+        Permissions::MANAGE_ROLES | Permissions::MANAGE_CHANNELS | Permissions::MANAGE_MESSAGES;
+    if let Some(guild) = guild_id.to_guild_cached(&ctx.cache) {
+        let bot_id = ctx.cache.read().user.id;
+        let missing = required - guild.read().member_permissions(bot_id);
+        if !missing.is_empty() {
+            problems.push(format!("The bot is missing permissions: {:?}", missing));
+        }
+    }
+
+    problems
+}
@@ -0,0 +1,25 @@
+use chrono_tz::Tz;
+use redis::{Commands, Connection, RedisResult};
+
+// Get the timezone times are entered and shown in, which defaults to UTC
+pub fn timezone(client: &mut Connection) -> RedisResult<Tz> {
+    let raw: Option<String> = client.get("event_timezone")?;
+    Ok(raw
+        .and_then(|raw| raw.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC))
+}
+
+// Change the timezone times are entered and shown in
+pub fn set_timezone(client: &mut Connection, timezone: Tz) -> RedisResult<()> {
+    client.set("event_timezone", timezone.name())
+}
+
+// Get when the event ends in milliseconds
+pub fn end(client: &mut Connection) -> RedisResult<Option<i64>> {
+    client.get("event_end")
+}
+
+// Change when the event ends
+pub fn set_end(client: &mut Connection, at: i64) -> RedisResult<()> {
+    client.set("event_end", at)
+}
@@ -0,0 +1,332 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A criterion projects are scored on
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Criterion {
+    pub name: String,
+    pub weight: f64,
+    pub scale: u32,
+}
+
+/// The scores a judge gave a table for each criterion
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub judge: u64,
+    pub table: String,
+    pub scores: HashMap<String, u32>,
+    pub updated_at: i64,
+}
+
+impl Score {
+    // The weighted score out of 100, ignoring criteria that are no longer in the rubric
+    pub fn total(&self, rubric: &[Criterion]) -> f64 {
+        let weights = rubric.iter().map(|c| c.weight).sum::<f64>();
+        if weights <= 0.0 {
+            return 0.0;
+        }
+
+        rubric
+            .iter()
+            .map(|c| {
+                let score = self.scores.get(&c.name).copied().unwrap_or(0);
+                c.weight * f64::from(score) / f64::from(c.scale)
+            })
+            .sum::<f64>()
+            / weights
+            * 100.0
+    }
+}
+
+/// A table's standing once every judge's scores are normalized
+#[derive(Clone)]
+pub struct Standing {
+    pub table: String,
+    pub judges: usize,
+    pub average: f64,
+    pub normalized: f64,
+}
+
+// Get the criteria in the order judges score them
+pub fn rubric(client: &mut Connection) -> RedisResult<Vec<Criterion>> {
+    let raw: Option<String> = client.get("rubric")?;
+    Ok(raw
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+// Replace the criteria
+pub fn set_rubric(client: &mut Connection, rubric: &[Criterion]) -> RedisResult<()> {
+    let raw = serde_json::to_string(rubric).expect("Failed to serialize rubric");
+    client.set("rubric", raw)
+}
+
+// Get the tracks projects can enter
+pub fn tracks(client: &mut Connection) -> RedisResult<Vec<String>> {
+    let mut tracks: Vec<String> = client.smembers("tracks")?;
+    tracks.sort();
+    Ok(tracks)
+}
+
+// Replace the tracks projects can enter
+pub fn set_tracks(client: &mut Connection, tracks: &[String]) -> RedisResult<()> {
+    let _: () = client.del("tracks")?;
+    if tracks.is_empty() {
+        return Ok(());
+    }
+    client.sadd("tracks", tracks)
+}
+
+// Record that a judge has a conflict of interest with a table, returning whether it is new
+pub fn add_conflict(client: &mut Connection, judge: u64, table: &str) -> RedisResult<bool> {
+    client.sadd(format!("judge_conflicts:{}", judge), table)
+}
+
+// Get the tables a judge has a conflict of interest with
+pub fn conflicts(client: &mut Connection, judge: u64) -> RedisResult<HashSet<String>> {
+    client.smembers(format!("judge_conflicts:{}", judge))
+}
+
+// Replace every judge's assigned tables
+pub fn set_assignments(
+    client: &mut Connection,
+    assignments: &HashMap<u64, Vec<String>>,
+) -> RedisResult<()> {
+    let _: () = client.del("judge_assignments")?;
+    for (judge, tables) in assignments {
+        let raw = serde_json::to_string(tables).expect("Failed to serialize assignments");
+        let _: () = client.hset("judge_assignments", *judge, raw)?;
+    }
+    Ok(())
+}
+
+// Get the tables assigned to a judge
+pub fn assignments(client: &mut Connection, judge: u64) -> RedisResult<Vec<String>> {
+    let raw: Option<String> = client.hget("judge_assignments", judge)?;
+    Ok(raw
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+// Get the tables assigned to every judge
+pub fn all_assignments(client: &mut Connection) -> RedisResult<HashMap<u64, Vec<String>>> {
+    let raw: HashMap<u64, String> = client.hgetall("judge_assignments")?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(judge, raw)| Some((judge, serde_json::from_str(&raw).ok()?)))
+        .collect())
+}
+
+// Persist a judge's scores for a table, replacing any previous ones
+pub fn save_score(client: &mut Connection, score: &Score) -> RedisResult<()> {
+    let raw = serde_json::to_string(score).expect("Failed to serialize score");
+    client.hset("scores", format!("{}:{}", score.judge, score.table), raw)
+}
+
+// Get every score
+pub fn scores(client: &mut Connection) -> RedisResult<Vec<Score>> {
+    let raw: Vec<String> = client.hvals("scores")?;
+    Ok(raw
+        .iter()
+        .filter_map(|raw| serde_json::from_str(raw).ok())
+        .collect())
+}
+
+// Keep only the scores judges gave tables they are still assigned and don't
+// have a conflict of interest with
+pub fn counted(
+    scores: Vec<Score>,
+    assignments: &HashMap<u64, Vec<String>>,
+    conflicts: &HashMap<u64, HashSet<String>>,
+) -> Vec<Score> {
+    scores
+        .into_iter()
+        .filter(|s| {
+            assignments
+                .get(&s.judge)
+                .is_some_and(|tables| tables.contains(&s.table))
+                && !conflicts
+                    .get(&s.judge)
+                    .is_some_and(|tables| tables.contains(&s.table))
+        })
+        .collect()
+}
+
+// Rank tables by the average of their scores after normalizing each judge's
+// scores, so harsh and generous judges count the same
+pub fn rank(rubric: &[Criterion], scores: &[Score]) -> Vec<Standing> {
+    let mut by_judge: HashMap<u64, Vec<(&str, f64)>> = HashMap::new();
+    for score in scores {
+        by_judge
+            .entry(score.judge)
+            .or_default()
+            .push((&score.table, score.total(rubric)));
+    }
+
+    let mut by_table: HashMap<&str, (Vec<f64>, Vec<f64>)> = HashMap::new();
+    for totals in by_judge.values() {
+        let count = totals.len() as f64;
+        let mean = totals.iter().map(|(_, t)| t).sum::<f64>() / count;
+        let deviation =
+            (totals.iter().map(|(_, t)| (t - mean).powi(2)).sum::<f64>() / count).sqrt();
+
+        for (table, total) in totals {
+            let normalized = if deviation > 0.0 {
+                (total - mean) / deviation
+            } else {
+                0.0
+            };
+            let entry = by_table.entry(table).or_default();
+            entry.0.push(*total);
+            entry.1.push(normalized);
+        }
+    }
+
+    let mut standings = by_table
+        .into_iter()
+        .map(|(table, (totals, normalized))| Standing {
+            table: table.to_string(),
+            judges: totals.len(),
+            average: totals.iter().sum::<f64>() / totals.len() as f64,
+            normalized: normalized.iter().sum::<f64>() / normalized.len() as f64,
+        })
+        .collect::<Vec<_>>();
+    standings.sort_by(|a, b| {
+        b.normalized
+            .partial_cmp(&a.normalized)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| {
+                b.average
+                    .partial_cmp(&a.average)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .then_with(|| a.table.cmp(&b.table))
+    });
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rubric() -> Vec<Criterion> {
+        vec![
+            Criterion {
+                name: "Idea".to_string(),
+                weight: 1.0,
+                scale: 10,
+            },
+            Criterion {
+                name: "Execution".to_string(),
+                weight: 3.0,
+                scale: 5,
+            },
+        ]
+    }
+
+    fn score(judge: u64, table: &str, idea: u32, execution: u32) -> Score {
+        let mut scores = HashMap::new();
+        scores.insert("Idea".to_string(), idea);
+        scores.insert("Execution".to_string(), execution);
+        Score {
+            judge,
+            table: table.to_string(),
+            scores,
+            updated_at: 0,
+        }
+    }
+
+    fn standing<'a>(standings: &'a [Standing], table: &str) -> &'a Standing {
+        standings.iter().find(|s| s.table == table).unwrap()
+    }
+
+    #[test]
+    fn total_weights_each_criterion_by_its_scale() {
+        let rubric = rubric();
+        assert_eq!(score(1, "Table 1", 10, 5).total(&rubric), 100.0);
+        assert_eq!(score(1, "Table 1", 0, 0).total(&rubric), 0.0);
+        // (1 * 10/10 + 3 * 0/5) / 4
+        assert_eq!(score(1, "Table 1", 10, 0).total(&rubric), 25.0);
+        // (1 * 0/10 + 3 * 5/5) / 4
+        assert_eq!(score(1, "Table 1", 0, 5).total(&rubric), 75.0);
+    }
+
+    #[test]
+    fn total_ignores_criteria_outside_the_rubric() {
+        let mut rubric = rubric();
+        rubric.remove(1);
+        let mut s = score(1, "Table 1", 5, 0);
+        s.scores.insert("Removed".to_string(), 3);
+        assert_eq!(s.total(&rubric), 50.0);
+
+        // Criteria added after scoring count as zero
+        s.scores.remove("Idea");
+        assert_eq!(s.total(&rubric), 0.0);
+        assert_eq!(s.total(&[]), 0.0);
+    }
+
+    #[test]
+    fn rank_normalizes_harsh_and_generous_judges() {
+        let rubric = rubric();
+        let scores = vec![
+            // A generous judge prefers table 2
+            score(1, "Table 1", 8, 4),
+            score(1, "Table 2", 10, 5),
+            // A harsh judge also prefers table 2
+            score(2, "Table 1", 0, 0),
+            score(2, "Table 2", 2, 1),
+        ];
+
+        let standings = rank(&rubric, &scores);
+        assert_eq!(standings[0].table, "Table 2");
+        assert_eq!(standings[1].table, "Table 1");
+        assert!((standing(&standings, "Table 2").normalized - 1.0).abs() < 1e-9);
+        assert!((standing(&standings, "Table 1").normalized + 1.0).abs() < 1e-9);
+        assert_eq!(standing(&standings, "Table 2").judges, 2);
+        // (100 + 20) / 2
+        assert!((standing(&standings, "Table 2").average - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rank_gives_judges_without_spread_no_weight() {
+        let rubric = rubric();
+        let scores = vec![
+            score(1, "Table 1", 10, 5),
+            score(2, "Table 1", 0, 0),
+            score(2, "Table 2", 0, 0),
+        ];
+
+        let standings = rank(&rubric, &scores);
+        assert_eq!(standing(&standings, "Table 1").normalized, 0.0);
+        assert_eq!(standing(&standings, "Table 2").normalized, 0.0);
+        // Ties fall back to the raw average
+        assert_eq!(standings[0].table, "Table 1");
+        assert!(rank(&rubric, &[]).is_empty());
+    }
+
+    #[test]
+    fn counted_drops_unassigned_and_conflicted_scores() {
+        let mut assignments = HashMap::new();
+        assignments.insert(1, vec!["Table 1".to_string(), "Table 2".to_string()]);
+        assignments.insert(2, vec!["Table 1".to_string()]);
+        let mut conflicts = HashMap::new();
+        conflicts.insert(1, vec!["Table 2".to_string()].into_iter().collect());
+
+        let scores = vec![
+            score(1, "Table 1", 1, 1),
+            score(1, "Table 2", 1, 1),
+            score(2, "Table 1", 1, 1),
+            score(2, "Table 3", 1, 1),
+            score(3, "Table 1", 1, 1),
+        ];
+        let kept = counted(scores, &assignments, &conflicts)
+            .into_iter()
+            .map(|s| (s.judge, s.table))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            vec![(1, "Table 1".to_string()), (2, "Table 1".to_string())]
+        );
+    }
+}
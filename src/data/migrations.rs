@@ -0,0 +1,99 @@
+use log::info;
+use redis::{Commands, Connection, ErrorKind, RedisError, RedisResult};
+
+/// The schema version the bot currently expects
+pub const SCHEMA_VERSION: u32 = 1;
+
+// A migration upgrades the data to its version, returning a description of each change.
+// When dry running, no changes are written.
+type Migration = fn(&mut Connection, bool) -> RedisResult<Vec<String>>;
+
+// All the migrations in the order they must be applied
+const MIGRATIONS: &[(u32, &str, Migration)] = &[(
+    1,
+    "Store help requests as hashes instead of positional lists",
+    help_requests_as_hashes,
+)];
+
+// Get the schema version of the data, defaulting to the original layout
+pub fn current_version(client: &mut Connection) -> RedisResult<u32> {
+    let version: Option<u32> = client.get("schema_version")?;
+    Ok(version.unwrap_or(0))
+}
+
+// Apply all the pending migrations in order, returning the version the data is at
+pub fn run(client: &mut Connection, dry_run: bool) -> RedisResult<u32> {
+    let version = current_version(client)?;
+    if version > SCHEMA_VERSION {
+        return Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Schema version is newer than supported",
+            format!("found {}, expected at most {}", version, SCHEMA_VERSION),
+        )));
+    }
+
+    let mut current = version;
+    for (target, description, migration) in MIGRATIONS.iter().filter(|m| m.0 > version) {
+        info!(
+            "{} migration to schema version {}: {}",
+            if dry_run { "Dry running" } else { "Running" },
+            target,
+            description
+        );
+
+        for change in migration(client, dry_run)? {
+            info!("  {}", change);
+        }
+
+        if !dry_run {
+            let _: () = client.set("schema_version", *target)?;
+        }
+        current = *target;
+    }
+
+    if current == version {
+        info!("Schema is up to date at version {}", version);
+    }
+
+    Ok(current)
+}
+
+// Version 1: convert `help_request:*` lists of (description, link, table, timestamp) into hashes
+fn help_requests_as_hashes(client: &mut Connection, dry_run: bool) -> RedisResult<Vec<String>> {
+    let keys: Vec<String> = client
+        .scan_match::<&str, String>("help_request:*")?
+        .collect();
+
+    let mut changes = Vec::new();
+    for key in keys {
+        let kind: String = redis::cmd("TYPE").arg(&key).query(client)?;
+        if kind != "list" {
+            continue;
+        }
+
+        let (description, link, table, at): (String, String, String, i64) =
+            client.lrange(&key, 0, 3)?;
+        changes.push(format!("Converting '{}' to a hash", key));
+        if dry_run {
+            continue;
+        }
+
+        redis::pipe()
+            .atomic()
+            .del(&key)
+            .ignore()
+            .hset_multiple(
+                &key,
+                &[
+                    ("description", description),
+                    ("link", link),
+                    ("table", table),
+                    ("requested_at", at.to_string()),
+                ],
+            )
+            .ignore()
+            .query::<()>(client)?;
+    }
+
+    Ok(changes)
+}
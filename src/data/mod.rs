@@ -6,19 +6,46 @@ use serenity::{
 };
 use std::sync::Arc;
 
+pub mod agenda;
+pub mod announcements;
+pub mod attendance;
+pub mod audit;
+pub mod automod;
+pub mod backup;
+pub mod cases;
+pub mod conduct;
+pub mod config;
+pub mod event;
+pub mod judging;
+pub mod migrations;
+pub mod moderation;
+pub mod modmail;
+pub mod onboarding;
+pub mod phases;
+pub mod profiles;
+pub mod ratelimit;
+pub mod roster;
+pub mod submissions;
+pub mod workshops;
+
 struct RedisConnection;
 
 impl TypeMapKey for RedisConnection {
     type Value = Client;
 }
 
+// Connect to Redis
+pub fn connect() -> Client {
+    match Client::open(crate::util::REDIS_URL.clone()) {
+        Ok(c) => c,
+        Err(e) => crate::util::fail(&format!("Failed to connect to redis: {}", e)),
+    }
+}
+
 // Initialize Redis connection and add to Serenity
 pub fn init(serenity: &Serenity) {
     // Connect to redis
-    let redis = match Client::open(crate::util::REDIS_URL.clone()) {
-        Ok(c) => c,
-        Err(e) => crate::util::fail(&format!("Failed to connect to redis: {}", e)),
-    };
+    let redis = connect();
 
     // Attach to discord client
     let mut data = serenity.data.write();
@@ -40,19 +67,25 @@ pub fn add_help_request(
     link: String,
     table: String,
     at: i64,
-) -> RedisResult<usize> {
+) -> RedisResult<()> {
     let request_key = format!("help_request:{}", random_string(8));
 
-    client.lpush(&request_key, at)?;
-    client.lpush(&request_key, table)?;
-    client.lpush(&request_key, link)?;
-    client.lpush(&request_key, description)
+    client.hset_multiple(
+        &request_key,
+        &[
+            ("description", description),
+            ("link", link),
+            ("table", table),
+            ("requested_at", at.to_string()),
+        ],
+    )
 }
 
 pub fn get_help_request(
     client: &mut Connection,
     key: &String,
 ) -> RedisResult<(String, String, String, i64)> {
-    let data: (String, String, String, i64) = client.lrange(key, 0, 4)?;
+    let data: (String, String, String, i64) =
+        client.hget(key, &["description", "link", "table", "requested_at"])?;
     Ok(data)
 }
@@ -0,0 +1,132 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Mark a moderator as on-call or not
+pub fn set_oncall(client: &mut Connection, user: u64, oncall: bool) -> RedisResult<()> {
+    if oncall {
+        client.sadd("oncall", user)
+    } else {
+        client.srem("oncall", user)
+    }
+}
+
+// Get all the moderators that are on-call
+pub fn oncall(client: &mut Connection) -> RedisResult<Vec<u64>> {
+    client.smembers("oncall")
+}
+
+// Set whether the server is in lockdown
+pub fn set_lockdown(client: &mut Connection, lockdown: bool) -> RedisResult<()> {
+    if lockdown {
+        client.set("lockdown", 1)
+    } else {
+        client.del("lockdown")
+    }
+}
+
+// Check whether the server is in lockdown
+pub fn lockdown(client: &mut Connection) -> RedisResult<bool> {
+    client.exists("lockdown")
+}
+
+// Remember a user that was quarantined while joining
+pub fn add_quarantined(client: &mut Connection, user: u64) -> RedisResult<()> {
+    client.sadd("quarantined", user)
+}
+
+// Forget a user that was quarantined, returning whether they were
+pub fn remove_quarantined(client: &mut Connection, user: u64) -> RedisResult<bool> {
+    client.srem("quarantined", user)
+}
+
+// Check whether a user is quarantined
+pub fn is_quarantined(client: &mut Connection, user: u64) -> RedisResult<bool> {
+    client.sismember("quarantined", user)
+}
+
+// Get all the users that are quarantined
+pub fn quarantined(client: &mut Connection) -> RedisResult<Vec<u64>> {
+    client.smembers("quarantined")
+}
+
+/// The kinds of actions moderators can take against a user
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionKind {
+    Warn,
+    Mute,
+    Unmute,
+    Kick,
+    Ban,
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ActionKind::Warn => "warn",
+            ActionKind::Mute => "mute",
+            ActionKind::Unmute => "unmute",
+            ActionKind::Kick => "kick",
+            ActionKind::Ban => "ban",
+        })
+    }
+}
+
+/// A record of an action taken against a user
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Action {
+    pub id: u64,
+    pub kind: ActionKind,
+    pub user: u64,
+    pub moderator: u64,
+    pub reason: String,
+    pub case: Option<u64>,
+    /// How long a mute lasts in milliseconds
+    pub duration: Option<i64>,
+    pub created_at: i64,
+}
+
+// Persist a new action, assigning it the next id and adding it to the user's history
+pub fn record(client: &mut Connection, mut action: Action) -> RedisResult<Action> {
+    action.id = client.incr("action_id", 1)?;
+    let raw = serde_json::to_string(&action).expect("Failed to serialize action");
+    let _: () = client.set(format!("action:{}", action.id), raw)?;
+    let _: () = client.zadd(format!("actions:{}", action.user), action.id, action.id)?;
+    Ok(action)
+}
+
+// Retrieve all the actions taken against a user, oldest first
+pub fn history(client: &mut Connection, user: u64) -> RedisResult<Vec<Action>> {
+    let ids: Vec<u64> = client.zrange(format!("actions:{}", user), 0, -1)?;
+
+    let mut actions = Vec::new();
+    for id in ids {
+        if let Some(action) = get(client, id)? {
+            actions.push(action);
+        }
+    }
+
+    Ok(actions)
+}
+
+// Retrieve an action by its id
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<Action>> {
+    let raw: Option<String> = client.get(format!("action:{}", id))?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Remember the mute that is currently in effect for a user
+pub fn set_mute(client: &mut Connection, user: u64, action: u64) -> RedisResult<()> {
+    client.hset("mutes", user, action)
+}
+
+// Get the mute that is currently in effect for a user
+pub fn mute(client: &mut Connection, user: u64) -> RedisResult<Option<u64>> {
+    client.hget("mutes", user)
+}
+
+// Forget the mute in effect for a user
+pub fn clear_mute(client: &mut Connection, user: u64) -> RedisResult<()> {
+    client.hdel("mutes", user)
+}
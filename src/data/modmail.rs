@@ -0,0 +1,33 @@
+use redis::{Commands, Connection, RedisResult};
+
+// Link a user to the channel their conversation with the moderators happens in
+pub fn open(client: &mut Connection, user: u64, channel: u64) -> RedisResult<()> {
+    let _: () = client.hset("modmail", user, channel)?;
+    client.hset("modmail_channels", channel, user)
+}
+
+// Remove the link between a user and their channel
+pub fn close(client: &mut Connection, user: u64, channel: u64) -> RedisResult<()> {
+    let _: () = client.hdel("modmail", user)?;
+    client.hdel("modmail_channels", channel)
+}
+
+// Get the channel of a user's open conversation
+pub fn channel_for(client: &mut Connection, user: u64) -> RedisResult<Option<u64>> {
+    client.hget("modmail", user)
+}
+
+// Get the user a conversation channel belongs to
+pub fn user_for(client: &mut Connection, channel: u64) -> RedisResult<Option<u64>> {
+    client.hget("modmail_channels", channel)
+}
+
+// Record the message the latest transcript of a user's conversation was archived in
+pub fn set_transcript(client: &mut Connection, user: u64, message: u64) -> RedisResult<()> {
+    client.hset("modmail_transcripts", user, message)
+}
+
+// Get the message the latest transcript of a user's conversation was archived in
+pub fn transcript(client: &mut Connection, user: u64) -> RedisResult<Option<u64>> {
+    client.hget("modmail_transcripts", user)
+}
@@ -0,0 +1,119 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::data::profiles::Profile;
+
+/// The profile field a step asks for
+#[derive(Clone, Copy)]
+pub enum Question {
+    Pronouns,
+    Timezone,
+    Skills,
+}
+
+impl Question {
+    /// Store an answer in a profile
+    pub fn answer(self, profile: &mut Profile, answer: &str) {
+        let answer = answer.trim();
+        match self {
+            Question::Pronouns => profile.pronouns = Some(answer.to_string()),
+            Question::Timezone => profile.timezone = Some(answer.to_string()),
+            Question::Skills => {
+                profile.skills = answer
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A direct message sent to new members
+pub struct Step {
+    pub name: &'static str,
+    pub text: &'static str,
+    pub question: Option<Question>,
+}
+
+/// The steps of onboarding in the order they are sent, with their default text
+pub const STEPS: &[Step] = &[
+    Step {
+        name: "welcome",
+        text: "Welcome to the hackathon! I have a few quick questions to set up your participant profile. Reply `skip` to skip any of them.",
+        question: None,
+    },
+    Step {
+        name: "pronouns",
+        text: "What are your pronouns?",
+        question: Some(Question::Pronouns),
+    },
+    Step {
+        name: "timezone",
+        text: "What timezone are you in? For example, `America/Los_Angeles` or `UTC-8`.",
+        question: Some(Question::Timezone),
+    },
+    Step {
+        name: "skills",
+        text: "What are your skills? Reply with a comma separated list, like `rust, design, machine learning`.",
+        question: Some(Question::Skills),
+    },
+    Step {
+        name: "commands",
+        text: "Thanks! Use `~join <table number>` to join your team's table and `~mentor request <description>, [<link>]` to get help from a mentor. Run `~help` to see everything else.",
+        question: None,
+    },
+    Step {
+        name: "schedule",
+        text: "",
+        question: None,
+    },
+];
+
+// Find a step by its name
+pub fn find(name: &str) -> Option<usize> {
+    STEPS.iter().position(|s| s.name == name)
+}
+
+// Get the text sent for a step, which is empty when it is disabled
+pub fn text(client: &mut Connection, step: usize) -> RedisResult<String> {
+    let custom: Option<String> = client.hget("onboarding_text", STEPS[step].name)?;
+    Ok(custom.unwrap_or_else(|| STEPS[step].text.to_string()))
+}
+
+// Change the text sent for a step
+pub fn set_text(client: &mut Connection, step: usize, text: &str) -> RedisResult<()> {
+    client.hset("onboarding_text", STEPS[step].name, text)
+}
+
+// Go back to the default text for a step
+pub fn reset_text(client: &mut Connection, step: usize) -> RedisResult<()> {
+    client.hdel("onboarding_text", STEPS[step].name)
+}
+
+// How long a member has to answer a question before their messages are no longer taken as answers
+const PROGRESS_TTL: usize = 60 * 60;
+
+/// The question a member is currently answering
+#[derive(Serialize, Deserialize)]
+pub struct Progress {
+    pub step: usize,
+    pub prompt: u64,
+}
+
+// Remember the question a member is currently answering and the message that asked it
+pub fn set_progress(client: &mut Connection, user: u64, progress: &Progress) -> RedisResult<()> {
+    let raw = serde_json::to_string(progress).expect("Failed to serialize progress");
+    client.set_ex(format!("onboarding:{}", user), raw, PROGRESS_TTL)
+}
+
+// Get the question a member is currently answering, if any
+pub fn progress(client: &mut Connection, user: u64) -> RedisResult<Option<Progress>> {
+    let raw: Option<String> = client.get(format!("onboarding:{}", user))?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Forget a member's progress once they are done
+pub fn finish(client: &mut Connection, user: u64) -> RedisResult<()> {
+    client.del(format!("onboarding:{}", user))
+}
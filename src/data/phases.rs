@@ -0,0 +1,92 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// The stages the event moves through
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    PreEvent,
+    TeamFormation,
+    Hacking,
+    Judging,
+    Closed,
+}
+
+impl Phase {
+    /// The announcement posted when the event enters the phase
+    pub fn announcement(self) -> &'static str {
+        match self {
+            Phase::PreEvent => "The event hasn't started yet, stay tuned!",
+            Phase::TeamFormation => {
+                "Team formation has started! Use `~join <table number>` to join your team's table."
+            }
+            Phase::Hacking => {
+                "Hacking has started! Good luck, and use `~mentor request` if you need any help. Submit your project with `~submit <link>, <title>`."
+            }
+            Phase::Judging => "Hacking is over! Submissions are closed and judging has started. Judges, use `~judge assignments` in a direct message to see your tables.",
+            Phase::Closed => "The event is over. Thanks for participating!",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Phase::PreEvent => "pre-event",
+            Phase::TeamFormation => "team formation",
+            Phase::Hacking => "hacking",
+            Phase::Judging => "judging",
+            Phase::Closed => "closed",
+        })
+    }
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace(' ', "-").as_str() {
+            "pre-event" | "pre" => Ok(Phase::PreEvent),
+            "team-formation" | "formation" => Ok(Phase::TeamFormation),
+            "hacking" => Ok(Phase::Hacking),
+            "judging" => Ok(Phase::Judging),
+            "closed" => Ok(Phase::Closed),
+            _ => Err(format!(
+                "'{}' is not one of pre-event, team-formation, hacking, judging, or closed",
+                s
+            )),
+        }
+    }
+}
+
+/// The phases the team management commands are allowed in
+pub const TABLES: &[Phase] = &[Phase::TeamFormation, Phase::Hacking];
+
+/// The phases the mentor commands are allowed in
+pub const MENTORS: &[Phase] = &[Phase::TeamFormation, Phase::Hacking];
+
+/// The phases projects can be submitted in
+pub const SUBMISSIONS: &[Phase] = &[Phase::Hacking];
+
+/// The phases judges can score projects in
+pub const JUDGING: &[Phase] = &[Phase::Judging];
+
+// Get the current phase, which is unset until the event is configured
+pub fn get(client: &mut Connection) -> RedisResult<Option<Phase>> {
+    let raw: Option<String> = client.get("phase")?;
+    Ok(raw.and_then(|raw| raw.parse::<Phase>().ok()))
+}
+
+// Move the event into a phase
+pub fn set(client: &mut Connection, phase: Phase) -> RedisResult<()> {
+    client.set("phase", phase.to_string())
+}
+
+// Check whether the event is in one of the phases, which is always the case until a phase is set
+pub fn is_in(client: &mut Connection, allowed: &[Phase]) -> RedisResult<bool> {
+    Ok(match get(client)? {
+        Some(phase) => allowed.contains(&phase),
+        None => true,
+    })
+}
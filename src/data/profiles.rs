@@ -0,0 +1,25 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+/// What a participant shared about themselves while onboarding
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub pronouns: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub skills: Vec<String>,
+}
+
+// Get a participant's profile
+pub fn get(client: &mut Connection, user: u64) -> RedisResult<Option<Profile>> {
+    let raw: Option<String> = client.hget("profiles", user)?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Persist a participant's profile
+pub fn save(client: &mut Connection, user: u64, profile: &Profile) -> RedisResult<()> {
+    let raw = serde_json::to_string(profile).expect("Failed to serialize profile");
+    client.hset("profiles", user, raw)
+}
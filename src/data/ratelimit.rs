@@ -0,0 +1,154 @@
+use redis::{Commands, Connection, RedisResult};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::util::{format_duration, parse_duration, random_string};
+
+/// The most times something can happen within a window of milliseconds
+#[derive(Clone, Copy)]
+pub struct Limit {
+    pub count: u32,
+    pub window: i64,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} per {}", self.count, format_duration(self.window))
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        match (
+            parts.next().and_then(|c| c.trim().parse::<u32>().ok()),
+            parts.next().and_then(parse_duration),
+        ) {
+            (Some(count), Some(window)) if count > 0 => Ok(Limit { count, window }),
+            _ => Err(format!("'{}' must be a count and a window like `5/10s`", s)),
+        }
+    }
+}
+
+/// The limits used when none have been configured
+pub const DEFAULTS: &[(&str, Limit)] = &[
+    (
+        "messages",
+        Limit {
+            count: 8,
+            window: 10_000,
+        },
+    ),
+    (
+        "commands",
+        Limit {
+            count: 5,
+            window: 30_000,
+        },
+    ),
+    (
+        "emergency",
+        Limit {
+            count: 1,
+            window: 300_000,
+        },
+    ),
+    (
+        "joins",
+        Limit {
+            count: 10,
+            window: 60_000,
+        },
+    ),
+    (
+        "verification",
+        Limit {
+            count: 5,
+            window: 900_000,
+        },
+    ),
+    (
+        "modmail_threads",
+        Limit {
+            count: 3,
+            window: 3_600_000,
+        },
+    ),
+];
+
+// How long rate limit violations count towards escalating the response
+const STRIKE_WINDOW: usize = 3600;
+
+// Get the limit for messages, all commands, or a specific command
+pub fn get(client: &mut Connection, name: &str) -> RedisResult<Limit> {
+    let raw: Option<String> = client.hget("rate_limits", name)?;
+    if let Some(limit) = raw.and_then(|raw| raw.parse::<Limit>().ok()) {
+        return Ok(limit);
+    }
+
+    match DEFAULTS.iter().find(|(n, _)| *n == name) {
+        Some((_, limit)) => Ok(*limit),
+        // Commands without their own limit share the general one
+        None => get(client, "commands"),
+    }
+}
+
+// Persist a limit
+pub fn set(client: &mut Connection, name: &str, limit: Limit) -> RedisResult<()> {
+    client.hset(
+        "rate_limits",
+        name,
+        format!("{}/{}s", limit.count, limit.window / 1000),
+    )
+}
+
+// Get all the limits in effect, including the defaults
+pub fn all(client: &mut Connection) -> RedisResult<BTreeMap<String, Limit>> {
+    let mut limits = DEFAULTS
+        .iter()
+        .map(|(name, limit)| (name.to_string(), *limit))
+        .collect::<BTreeMap<_, _>>();
+
+    let raw: BTreeMap<String, String> = client.hgetall("rate_limits")?;
+    for (name, raw) in raw {
+        if let Ok(limit) = raw.parse::<Limit>() {
+            limits.insert(name, limit);
+        }
+    }
+
+    Ok(limits)
+}
+
+// Record an event in a sliding window, returning how many happened within it
+pub fn hit(client: &mut Connection, key: &str, limit: Limit, now: i64) -> RedisResult<u32> {
+    let key = format!("ratelimit:{}", key);
+    let (_, _, count, _): ((), (), u32, ()) = redis::pipe()
+        .atomic()
+        .zrembyscore(&key, "-inf", now - limit.window)
+        .zadd(&key, format!("{}-{}", now, random_string(4)), now)
+        .zcard(&key)
+        .expire(&key, (limit.window / 1000) as usize + 1)
+        .query(client)?;
+    Ok(count)
+}
+
+// Count the events recorded in a sliding window without recording a new one
+pub fn count(client: &mut Connection, key: &str, limit: Limit, now: i64) -> RedisResult<u32> {
+    client.zcount(
+        format!("ratelimit:{}", key),
+        format!("({}", now - limit.window),
+        "+inf",
+    )
+}
+
+// Record a violation for a user, returning how many they have made recently
+pub fn strike(client: &mut Connection, user: u64) -> RedisResult<u32> {
+    let key = format!("ratelimit_strikes:{}", user);
+    let (count, _): (u32, ()) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, STRIKE_WINDOW)
+        .query(client)?;
+    Ok(count)
+}
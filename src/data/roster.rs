@@ -0,0 +1,163 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+use crate::util::split_csv_line;
+
+/// Someone who registered for the event
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Registrant {
+    pub name: String,
+    pub email: String,
+    pub code: String,
+}
+
+// Codes are compared without regard to case or surrounding whitespace
+pub fn normalize(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+// Parse a roster from CSV with the columns name, email, and ticket code
+pub fn parse(raw: &str) -> Result<Vec<Registrant>, String> {
+    let mut registrants = Vec::new();
+
+    for (number, line) in raw.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        if fields.len() != 3 {
+            return Err(format!(
+                "Line {} has {} columns, but name, email, and ticket code are required",
+                number + 1,
+                fields.len()
+            ));
+        }
+
+        // Skip the header if there is one
+        if number == 0 && fields[1].eq_ignore_ascii_case("email") {
+            continue;
+        }
+
+        let code = normalize(&fields[2]);
+        if code.is_empty() {
+            return Err(format!("Line {} is missing a ticket code", number + 1));
+        }
+        registrants.push(Registrant {
+            name: fields[0].trim().to_string(),
+            email: fields[1].trim().to_string(),
+            code,
+        });
+    }
+
+    Ok(registrants)
+}
+
+// Add registrants to the roster, replacing any with the same code
+pub fn import(client: &mut Connection, registrants: &[Registrant]) -> RedisResult<()> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    for registrant in registrants {
+        let raw = serde_json::to_string(registrant).expect("Failed to serialize registrant");
+        pipe.hset("roster", &registrant.code, raw).ignore();
+    }
+    pipe.query(client)
+}
+
+// Check whether a roster has been imported
+pub fn is_enabled(client: &mut Connection) -> RedisResult<bool> {
+    client.exists("roster")
+}
+
+// Find a registrant by their ticket code
+pub fn get(client: &mut Connection, code: &str) -> RedisResult<Option<Registrant>> {
+    let raw: Option<String> = client.hget("roster", normalize(code))?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Claim a ticket code for a user, returning whether it was still available
+pub fn claim(client: &mut Connection, code: &str, user: u64) -> RedisResult<bool> {
+    let claimed: bool = client.hset_nx("roster_claims", normalize(code), user)?;
+    if claimed {
+        let _: () = client.hset("roster_users", user, normalize(code))?;
+    }
+    Ok(claimed)
+}
+
+// Release a ticket code a user claimed so it can be used again
+pub fn unclaim(client: &mut Connection, code: &str, user: u64) -> RedisResult<()> {
+    redis::pipe()
+        .atomic()
+        .hdel("roster_claims", normalize(code))
+        .ignore()
+        .hdel("roster_users", user)
+        .ignore()
+        .query(client)
+}
+
+// Get the ticket code a user claimed
+pub fn claimed_by(client: &mut Connection, user: u64) -> RedisResult<Option<String>> {
+    client.hget("roster_users", user)
+}
+
+// Count the registrants and how many have claimed their code
+pub fn counts(client: &mut Connection) -> RedisResult<(usize, usize)> {
+    let total: usize = client.hlen("roster")?;
+    let claimed: usize = client.hlen("roster_claims")?;
+    Ok((total, claimed))
+}
+
+// Remember a member that has joined but not yet verified
+pub fn add_pending(client: &mut Connection, user: u64) -> RedisResult<()> {
+    client.sadd("unverified", user)
+}
+
+// Forget a member that is waiting to verify
+pub fn remove_pending(client: &mut Connection, user: u64) -> RedisResult<()> {
+    client.srem("unverified", user)
+}
+
+// Check whether a member is waiting to verify
+pub fn is_pending(client: &mut Connection, user: u64) -> RedisResult<bool> {
+    client.sismember("unverified", user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_registrants() {
+        let registrants = parse(
+            "Name,Email,Ticket Code\n\"Doe, Jane\", jane@example.com , abc123 \n\nJohn,john@example.com,XYZ789\n",
+        )
+        .unwrap();
+        assert_eq!(registrants.len(), 2);
+        assert_eq!(registrants[0].name, "Doe, Jane");
+        assert_eq!(registrants[0].email, "jane@example.com");
+        assert_eq!(registrants[0].code, "ABC123");
+        assert_eq!(registrants[1].code, "XYZ789");
+    }
+
+    #[test]
+    fn parse_only_skips_a_header_on_the_first_line() {
+        let registrants = parse("Jane,jane@example.com,ABC123\nName,Email,Code").unwrap();
+        assert_eq!(registrants.len(), 2);
+        assert_eq!(registrants[1].code, "CODE");
+    }
+
+    #[test]
+    fn parse_reports_the_invalid_line() {
+        let why = parse("Jane,jane@example.com,ABC123\nJohn,john@example.com")
+            .err()
+            .unwrap();
+        assert!(why.starts_with("Line 2 has 2 columns"));
+        let why = parse("Jane,jane@example.com,  ").err().unwrap();
+        assert_eq!(why, "Line 1 is missing a ticket code");
+    }
+
+    #[test]
+    fn codes_ignore_case_and_whitespace() {
+        assert_eq!(normalize(" abc123\n"), "ABC123");
+    }
+}
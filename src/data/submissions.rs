@@ -0,0 +1,55 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A table's project
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub table: String,
+    pub link: String,
+    pub title: String,
+    pub submitted_by: u64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub tracks: Vec<String>,
+}
+
+// Persist a table's submission, replacing any previous one
+pub fn save(client: &mut Connection, submission: &Submission) -> RedisResult<()> {
+    let raw = serde_json::to_string(submission).expect("Failed to serialize submission");
+    client.hset("submissions", &submission.table, raw)
+}
+
+// Get the submission of a table
+pub fn get(client: &mut Connection, table: &str) -> RedisResult<Option<Submission>> {
+    let raw: Option<String> = client.hget("submissions", table)?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Get every submission by table
+pub fn all(client: &mut Connection) -> RedisResult<HashMap<String, Submission>> {
+    let raw: HashMap<String, String> = client.hgetall("submissions")?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(table, raw)| Some((table, serde_json::from_str(&raw).ok()?)))
+        .collect())
+}
+
+// Sort table names by their number
+pub fn sort_tables(tables: &mut [String]) {
+    tables.sort_by_key(|t| {
+        t.trim_start_matches("Table ")
+            .parse::<i64>()
+            .unwrap_or(i64::MAX)
+    });
+}
+
+// Set when submissions are locked
+pub fn set_deadline(client: &mut Connection, at: i64) -> RedisResult<()> {
+    client.set("submission_deadline", at)
+}
+
+// Get when submissions are locked, if there is a deadline
+pub fn deadline(client: &mut Connection) -> RedisResult<Option<i64>> {
+    client.get("submission_deadline")
+}
@@ -0,0 +1,115 @@
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+
+/// An item on the agenda that participants sign up for
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Workshop {
+    pub id: u64,
+    pub host: u64,
+    pub capacity: usize,
+    pub role: u64,
+    pub channel: u64,
+}
+
+// Persist a workshop, which shares its id with its agenda item
+pub fn save(client: &mut Connection, workshop: &Workshop) -> RedisResult<()> {
+    let raw = serde_json::to_string(workshop).expect("Failed to serialize workshop");
+    client.hset("workshops", workshop.id, raw)
+}
+
+// Get a workshop by its id
+pub fn get(client: &mut Connection, id: u64) -> RedisResult<Option<Workshop>> {
+    let raw: Option<String> = client.hget("workshops", id)?;
+    Ok(raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+}
+
+// Get every workshop
+pub fn all(client: &mut Connection) -> RedisResult<Vec<Workshop>> {
+    let raw: Vec<String> = client.hvals("workshops")?;
+    Ok(raw
+        .iter()
+        .filter_map(|raw| serde_json::from_str(raw).ok())
+        .collect())
+}
+
+// Delete a workshop along with its attendees and waitlist
+pub fn remove(client: &mut Connection, id: u64) -> RedisResult<()> {
+    let _: () = client.del(&[
+        format!("workshop_attendees:{}", id),
+        format!("workshop_waitlist:{}", id),
+    ])?;
+    client.hdel("workshops", id)
+}
+
+// Get the attendees of a workshop in the order they signed up
+pub fn attendees(client: &mut Connection, id: u64) -> RedisResult<Vec<u64>> {
+    client.zrange(format!("workshop_attendees:{}", id), 0, -1)
+}
+
+// Get the waitlist of a workshop in the order they signed up
+pub fn waitlist(client: &mut Connection, id: u64) -> RedisResult<Vec<u64>> {
+    client.zrange(format!("workshop_waitlist:{}", id), 0, -1)
+}
+
+// Give a user a seat if one is left, returning whether they got it
+pub fn take_seat(
+    client: &mut Connection,
+    id: u64,
+    user: u64,
+    capacity: usize,
+    at: i64,
+) -> RedisResult<bool> {
+    // Counting and adding in one script keeps two sign-ups from taking the last seat
+    redis::Script::new(
+        r"
+        if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[1]) then return 0 end
+        redis.call('ZADD', KEYS[1], ARGV[3], ARGV[2])
+        return 1
+        ",
+    )
+    .key(format!("workshop_attendees:{}", id))
+    .arg(capacity)
+    .arg(user)
+    .arg(at)
+    .invoke(client)
+}
+
+// Add a user to the end of the waitlist, returning their position
+pub fn add_waitlisted(client: &mut Connection, id: u64, user: u64, at: i64) -> RedisResult<usize> {
+    let key = format!("workshop_waitlist:{}", id);
+    let _: () = client.zadd(&key, user, at)?;
+    client.zcard(&key)
+}
+
+// Remove a user from the attendees, returning whether they were attending
+pub fn remove_attendee(client: &mut Connection, id: u64, user: u64) -> RedisResult<bool> {
+    client.zrem(format!("workshop_attendees:{}", id), user)
+}
+
+// Remove a user from the waitlist, returning whether they were on it
+pub fn remove_waitlisted(client: &mut Connection, id: u64, user: u64) -> RedisResult<bool> {
+    client.zrem(format!("workshop_waitlist:{}", id), user)
+}
+
+// Move the first user on the waitlist into a seat if one is left, returning who moved
+pub fn promote_waitlisted(
+    client: &mut Connection,
+    id: u64,
+    capacity: usize,
+    at: i64,
+) -> RedisResult<Option<u64>> {
+    redis::Script::new(
+        r"
+        if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[1]) then return false end
+        local popped = redis.call('ZPOPMIN', KEYS[2])
+        if #popped == 0 then return false end
+        redis.call('ZADD', KEYS[1], ARGV[2], popped[1])
+        return popped[1]
+        ",
+    )
+    .key(format!("workshop_attendees:{}", id))
+    .key(format!("workshop_waitlist:{}", id))
+    .arg(capacity)
+    .arg(at)
+    .invoke(client)
+}
@@ -1,32 +1,38 @@
 #[macro_use]
 extern crate lazy_static;
 
-use std::{collections::HashSet, process::exit, sync::Arc};
+use std::{collections::HashSet, env, fs, process::exit, sync::Arc};
 
 use dotenv::dotenv;
-use log::{error, info};
+use log::{error, info, warn};
 use serenity::{
     client::bridge::gateway::ShardManager,
     framework::standard::{
         help_commands,
         macros::{group, help},
-        Args, CommandGroup, CommandResult, HelpOptions, StandardFramework,
+        Args, CommandGroup, CommandResult, DispatchError, HelpOptions, Reason, StandardFramework,
     },
     model::{
-        channel::Message,
-        event::ResumedEvent,
+        channel::{Message, Reaction},
+        event::{MessageUpdateEvent, ResumedEvent},
         gateway::{Activity, Ready},
         guild::Member,
-        id::{GuildId, RoleId, UserId},
+        id::{ChannelId, GuildId, MessageId, UserId},
     },
     prelude::*,
 };
 
 mod commands;
 mod data;
+mod scheduler;
 mod util;
 
-use commands::{admin::*, mentors::*, moderation::*, tables::*};
+use commands::{
+    actions::*, admin::*, agenda::*, announcements::*, attendance::*, audit::*, automod::*,
+    checks::*, conduct::*, event::*, judging::*, mentors::*, moderation::*, moderators::*,
+    modmail::*, onboarding::*, phases::*, raid::*, ratelimit::*, submissions::*, tables::*,
+    verify::*, workshops::*,
+};
 
 // Discord events handler
 struct Handler;
@@ -35,7 +41,60 @@ impl EventHandler for Handler {
     // Triggers when the client is ready & connected
     fn ready(&self, ctx: Context, ready: Ready) {
         info!("Connected as {}", ready.user.name);
-        ctx.set_activity(Activity::playing("~help"))
+        ctx.set_activity(Activity::playing("~help"));
+
+        // Start running scheduled jobs
+        scheduler::start(ctx);
+    }
+
+    // Triggers when all the guilds have been cached
+    fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
+        let values =
+            match data::get_connection(&ctx.data).and_then(|mut c| data::config::all(&mut c)) {
+                Ok(values) => values,
+                Err(e) => {
+                    error!("Failed to load configuration: {}", e);
+                    return;
+                }
+            };
+
+        // Prefer the guild the configuration refers to
+        let guild_id = match values.get("everyone_role") {
+            Some(id) => GuildId(*id),
+            None => match guilds.first() {
+                Some(id) => *id,
+                None => return,
+            },
+        };
+
+        // Check the configuration against the guild
+        let problems = data::config::verify(&ctx, guild_id, &values);
+        if problems.is_empty() {
+            info!("Configuration successfully verified");
+            return;
+        }
+        for problem in &problems {
+            warn!("Configuration problem: {}", problem);
+        }
+
+        // Notify the moderators if the reports channel is usable
+        let reports_channel = values.get("reports_channel").filter(|id| {
+            data::config::find("reports_channel")
+                .map(|setting| data::config::validate(&ctx, guild_id, setting, **id).is_ok())
+                .unwrap_or(false)
+        });
+        if let Some(channel) = reports_channel {
+            match ChannelId(*channel).send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.title("Configuration problems")
+                        .description(problems.join("\n"))
+                        .footer(|f| f.text("Use `~admin config set` or `~admin setup` to fix them"))
+                })
+            }) {
+                Ok(_) => {}
+                Err(e) => error!("Failed to send message: {}", e),
+            };
+        }
     }
 
     // Triggers when a connection is resumed
@@ -44,16 +103,76 @@ impl EventHandler for Handler {
         ctx.set_activity(Activity::playing("~help"));
     }
 
+    // Triggers when a message is sent
+    fn message(&self, ctx: Context, msg: Message) {
+        // Messages removed for flooding don't need to be scanned
+        match commands::ratelimit::check_message(&ctx, &msg) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => error!("Failed to check message rate limit: {:?}", e),
+        };
+
+        if let Err(e) = commands::automod::scan(&ctx, &msg) {
+            error!("Failed to scan message with automod: {:?}", e);
+        }
+        if let Err(e) = commands::audit::record(&ctx, &msg) {
+            error!("Failed to cache message for the audit log: {:?}", e);
+        }
+    }
+
+    // Triggers when a message is edited
+    fn message_update(
+        &self,
+        ctx: Context,
+        _: Option<Message>,
+        _: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        if let Err(e) = commands::audit::edited(&ctx, &event) {
+            error!("Failed to log edited message: {:?}", e);
+        }
+    }
+
+    // Triggers when a message is deleted
+    fn message_delete(&self, ctx: Context, _: ChannelId, message: MessageId) {
+        if let Err(e) = commands::audit::deleted(&ctx, message) {
+            error!("Failed to log deleted message: {:?}", e);
+        }
+    }
+
+    // Triggers when multiple messages are deleted at once
+    fn message_delete_bulk(&self, ctx: Context, _: ChannelId, messages: Vec<MessageId>) {
+        for message in messages {
+            if let Err(e) = commands::audit::deleted(&ctx, message) {
+                error!("Failed to log deleted message: {:?}", e);
+            }
+        }
+    }
+
+    // Triggers when a reaction is added to a message
+    fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        // Ignore the bot's own reactions
+        if reaction.user_id == ctx.cache.read().user.id {
+            return;
+        }
+
+        if let Err(e) = commands::moderators::acknowledge_reaction(&ctx, &reaction) {
+            error!("Failed to acknowledge case from reaction: {:?}", e);
+        }
+        if let Err(e) = commands::conduct::accept_reaction(&ctx, &reaction) {
+            error!("Failed to accept code of conduct from reaction: {:?}", e);
+        }
+    }
+
     // Triggers when a user joins the server
     fn guild_member_addition(&self, ctx: Context, _: GuildId, mut member: Member) {
-        match member.add_role(ctx.http, RoleId(util::TEAMLESS_ROLE_ID.clone())) {
-            Ok(_) => {}
-            Err(e) => error!(
-                "Failed to add teamless role to new user '{}': {}",
+        if let Err(e) = commands::raid::member_joined(&ctx, &mut member) {
+            error!(
+                "Failed to set up new user '{}': {:?}",
                 member.user.read().name,
                 e
-            ),
-        };
+            );
+        }
     }
 }
 
@@ -67,26 +186,105 @@ impl TypeMapKey for ShardManagerContainer {
 #[group]
 #[commands(join, leave)]
 #[description = "Manage your participation in a team"]
+#[checks(TablesPhase)]
 struct Tables;
 
+#[group]
+#[commands(verify, accept, profile, checkin)]
+#[description = "Get access to the server"]
+struct Registration;
+
+#[group]
+#[commands(schedule)]
+#[description = "Find out what is happening during the event"]
+struct Schedule;
+
+#[group]
+#[commands(submit)]
+#[description = "Submit your table's project"]
+#[checks(SubmissionsPhase)]
+struct Submissions;
+
+#[group]
+#[commands(judge_assignments, judge_score)]
+#[description = "Score projects as a judge"]
+#[prefixes("judge")]
+#[checks(Judge, JudgingPhase)]
+struct Judging;
+
+#[group]
+#[commands(workshop_list, workshop_rsvp, workshop_cancel, workshop_attendees)]
+#[description = "Sign up for workshops"]
+#[prefixes("workshop", "w")]
+struct Workshops;
+
 #[group]
 #[commands(request, list, complete)]
 #[description = "Commands to interact with mentors"]
 #[prefixes("m", "mentor")]
+#[checks(MentorsPhase)]
 struct Mentors;
 
 #[group]
-#[commands(shutdown)]
+#[commands(
+    shutdown,
+    config,
+    setup,
+    export,
+    import,
+    ratelimit,
+    audit,
+    roster,
+    coc,
+    onboarding,
+    checkin_admin,
+    attendance,
+    phase,
+    event,
+    announce,
+    agenda_admin,
+    workshop_admin,
+    submissions_admin,
+    rubric_admin,
+    judging_admin
+)]
 #[help_available(false)]
 #[description = "Admin only commands"]
 #[prefixes("a", "admin")]
 struct Admin;
 
 #[group]
-#[commands(report, emergency)]
+#[commands(report, anonymous, followup, emergency)]
 #[description = "Different levels for reporting a message"]
 struct Moderation;
 
+#[group]
+#[commands(
+    case, ack, oncall, modmail, warn, mute, unmute, kick, ban, history, automod, lockdown, release
+)]
+#[description = "Moderator only commands"]
+#[prefixes("mod")]
+struct Moderators;
+
+// Every group of commands, in the order they are matched
+const GROUPS: &[&CommandGroup] = &[
+    &REGISTRATION_GROUP,
+    &TABLES_GROUP,
+    &MENTORS_GROUP,
+    &SCHEDULE_GROUP,
+    &WORKSHOPS_GROUP,
+    &SUBMISSIONS_GROUP,
+    &JUDGING_GROUP,
+    &ADMIN_GROUP,
+    &MODERATION_GROUP,
+    &MODERATORS_GROUP,
+];
+
+// Register command handlers for every group
+fn register_groups(framework: StandardFramework) -> StandardFramework {
+    GROUPS.iter().fold(framework, |f, group| f.group(group))
+}
+
 fn main() {
     // Load configuration from a .env file
     // See .env.example for the required fields
@@ -96,6 +294,16 @@ fn main() {
     // Set RUST_LOG to the minimum level to log at
     env_logger::init();
 
+    // Run a command line subcommand instead of the bot if requested
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 {
+        run_subcommand(&args[1..]);
+        return;
+    }
+
+    // Report every invalid environment variable at once
+    util::check_environment();
+
     // Create client
     let mut client = match Client::new(util::DISCORD_TOKEN.clone(), Handler) {
         Ok(client) => client,
@@ -105,10 +313,26 @@ fn main() {
     // Connect to redis
     data::init(&client);
 
+    // Upgrade the data to the current schema
+    match data::get_connection(&client.data).and_then(|mut c| data::migrations::run(&mut c, false))
+    {
+        Ok(_) => {}
+        Err(e) => util::fail(&format!("Failed to migrate data: {}", e)),
+    };
+
+    // Seed any unset configuration values from the environment
+    match data::get_connection(&client.data).and_then(|mut c| data::config::seed(&mut c)) {
+        Ok(_) => {}
+        Err(e) => util::fail(&format!("Failed to seed configuration: {}", e)),
+    };
+
     // Attach shard manager
     {
         let mut data = client.data.write();
         data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
+        data.insert::<commands::ratelimit::CommandPaths>(commands::ratelimit::command_paths(
+            GROUPS,
+        ));
     }
 
     // Retrieve the owners and id
@@ -122,7 +346,7 @@ fn main() {
     };
 
     // Configure the client
-    client.with_framework(
+    client.with_framework(register_groups(
         StandardFramework::new()
             .configure(|c| {
                 c.with_whitespace(true)
@@ -132,13 +356,13 @@ fn main() {
                     .ignore_bots(true)
                     .owners(owners)
             })
-            // Log before execution
-            .before(|_, msg, command_name| {
+            // Log before execution and enforce the rate limits
+            .before(|ctx, msg, command_name| {
                 info!(
                     "Got command '{}' by user '{}'",
                     command_name, msg.author.name
                 );
-                true
+                commands::ratelimit::allow_command(ctx, msg, command_name)
             })
             // Log errors if occurred
             .after(|ctx, msg, command_name, error| {
@@ -161,13 +385,43 @@ fn main() {
                     );
                     match msg.channel_id.say(
                         &ctx.http,
-                        &format!("Command '{}' failed: internal server error", command_name),
+                        format!("Command '{}' failed: internal server error", command_name),
                     ) {
                         Ok(_) => {}
                         Err(e) => error!("Failed to send message: {}", e),
                     };
                 }
             })
+            // Explain why a command couldn't be run
+            .on_dispatch_error(|ctx, msg, error| {
+                let reason = match error {
+                    DispatchError::CheckFailed(_, Reason::User(reason)) => reason,
+                    DispatchError::CheckFailed(_, Reason::Log(reason)) => {
+                        error!("Check failed for user '{}': {}", msg.author.name, reason);
+                        "Command failed: internal server error".to_string()
+                    }
+                    DispatchError::LackingPermissions(_) => {
+                        "You don't have permission to run this command!".to_string()
+                    }
+                    DispatchError::OnlyForGuilds => {
+                        "This command can only be run in a server".to_string()
+                    }
+                    DispatchError::NotEnoughArguments { min, given } => format!(
+                        "This command requires at least {} arguments, but got {}",
+                        min, given
+                    ),
+                    DispatchError::TooManyArguments { max, given } => format!(
+                        "This command takes at most {} arguments, but got {}",
+                        max, given
+                    ),
+                    _ => return,
+                };
+
+                match msg.channel_id.say(&ctx.http, reason) {
+                    Ok(_) => {}
+                    Err(e) => error!("Failed to send message: {}", e),
+                };
+            })
             // Log unrecognized commands
             .unrecognised_command(|ctx, msg, unknown_command| {
                 info!(
@@ -177,12 +431,39 @@ fn main() {
 
                 match msg
                     .channel_id
-                    .say(&ctx.http, &format!("Unknown command '{}'", unknown_command))
+                    .say(&ctx.http, format!("Unknown command '{}'", unknown_command))
                 {
                     Ok(_) => {}
                     Err(e) => error!("Failed to send message: {}", e),
                 };
             })
+            // Handle verification replies and relay messages for modmail conversations
+            .normal_message(|ctx, msg| {
+                match commands::verify::handle_reply(ctx, msg) {
+                    Ok(true) => return,
+                    Ok(false) => {}
+                    Err(e) => error!(
+                        "Failed to verify user '{}' from reply: {:?}",
+                        msg.author.name, e
+                    ),
+                };
+
+                match commands::onboarding::handle_reply(ctx, msg) {
+                    Ok(true) => return,
+                    Ok(false) => {}
+                    Err(e) => error!(
+                        "Failed to record onboarding answer from user '{}': {:?}",
+                        msg.author.name, e
+                    ),
+                };
+
+                if let Err(e) = commands::modmail::relay(ctx, msg) {
+                    error!(
+                        "Failed to relay modmail message from user '{}': {:?}",
+                        msg.author.name, e
+                    );
+                }
+            })
             // Redirect caller to ~help command
             .prefix_only(|ctx, msg| {
                 match msg
@@ -194,12 +475,8 @@ fn main() {
                 };
             })
             // Register command handlers
-            .help(&DISPLAY_HELP)
-            .group(&TABLES_GROUP)
-            .group(&MENTORS_GROUP)
-            .group(&ADMIN_GROUP)
-            .group(&MODERATION_GROUP),
-    );
+            .help(&DISPLAY_HELP),
+    ));
 
     // Attempt to start the client
     if let Err(why) = client.start() {
@@ -208,6 +485,60 @@ fn main() {
     }
 }
 
+// Handle the command line subcommands
+fn run_subcommand(args: &[String]) {
+    match (args[0].as_str(), args.get(1)) {
+        ("import", Some(path)) => {
+            let raw = match fs::read(path) {
+                Ok(raw) => raw,
+                Err(e) => util::fail(&format!("Failed to read '{}': {}", path, e)),
+            };
+            let snapshot = match data::backup::parse(&raw) {
+                Ok(snapshot) => snapshot,
+                Err(e) => util::fail(&e),
+            };
+
+            let mut client = match data::connect().get_connection() {
+                Ok(client) => client,
+                Err(e) => util::fail(&format!("Failed to connect to redis: {}", e)),
+            };
+            match data::backup::is_empty(&mut client) {
+                Ok(true) => {}
+                Ok(false) => util::fail("The database must be empty to import a snapshot"),
+                Err(e) => util::fail(&format!("Failed to query redis: {}", e)),
+            };
+
+            match data::backup::import(&mut client, &snapshot) {
+                Ok(count) => println!("Successfully restored {} keys", count),
+                Err(e) => util::fail(&format!("Failed to restore snapshot: {}", e)),
+            };
+
+            // Bring older snapshots up to date
+            match data::migrations::run(&mut client, false) {
+                Ok(version) => println!("Schema is at version {}", version),
+                Err(e) => util::fail(&format!("Failed to migrate data: {}", e)),
+            };
+        }
+        ("migrate", flag) => {
+            let dry_run = match flag.map(String::as_str) {
+                None => false,
+                Some("--dry-run") => true,
+                Some(_) => util::fail("Usage: svh-bot migrate [--dry-run]"),
+            };
+
+            let mut client = match data::connect().get_connection() {
+                Ok(client) => client,
+                Err(e) => util::fail(&format!("Failed to connect to redis: {}", e)),
+            };
+            match data::migrations::run(&mut client, dry_run) {
+                Ok(version) => println!("Schema is at version {}", version),
+                Err(e) => util::fail(&format!("Failed to migrate data: {}", e)),
+            };
+        }
+        _ => util::fail("Usage: svh-bot [import <snapshot.json> | migrate [--dry-run]]"),
+    }
+}
+
 #[help]
 #[individual_command_tip = "All command arguments are separated by a `,`.\nTo get help with an individual command, pass its name as an argument to this command."]
 #[command_not_found_text = "Command not found: `{}`"]
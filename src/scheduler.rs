@@ -0,0 +1,107 @@
+use chrono::prelude::*;
+use log::error;
+use redis::{Commands, Connection, RedisResult};
+use serde::{Deserialize, Serialize};
+use serenity::{framework::standard::CommandResult, prelude::Context};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use crate::data::{get_connection, phases::Phase};
+
+/// Work to be done at a later time
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    Repage { case: u64, attempt: u32 },
+    Unmute { user: u64, action: u64 },
+    Phase { phase: Phase },
+    Announce { id: u64 },
+    Remind { item: u64 },
+    WorkshopReminder { workshop: u64 },
+    WorkshopEnd { workshop: u64 },
+}
+
+// How often to check for jobs that are due
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Whether the background thread has been started
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// Schedule a job to run at a timestamp in milliseconds
+pub fn schedule(client: &mut Connection, at: i64, job: &Job) -> RedisResult<()> {
+    let raw = serde_json::to_string(job).expect("Failed to serialize job");
+    client.zadd("jobs", raw, at)
+}
+
+// Cancel a scheduled job, returning whether it was still pending
+pub fn cancel(client: &mut Connection, job: &Job) -> RedisResult<bool> {
+    let raw = serde_json::to_string(job).expect("Failed to serialize job");
+    client.zrem("jobs", raw)
+}
+
+// Get all the jobs that haven't run yet along with when they are due
+pub fn pending(client: &mut Connection) -> RedisResult<Vec<(i64, Job)>> {
+    let all: Vec<(String, i64)> = client.zrange_withscores("jobs", 0, -1)?;
+    Ok(all
+        .into_iter()
+        .filter_map(|(raw, at)| Some((at, serde_json::from_str(&raw).ok()?)))
+        .collect())
+}
+
+// Start running jobs in the background, this only has an effect the first time it is called
+pub fn start(ctx: Context) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    thread::spawn(move || loop {
+        if let Err(e) = run_due(&ctx) {
+            error!("Failed to run scheduled jobs: {}", e);
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+// Run all the jobs that are due
+fn run_due(ctx: &Context) -> RedisResult<()> {
+    let mut client = get_connection(&ctx.data)?;
+    let due: Vec<String> = client.zrangebyscore("jobs", "-inf", Utc::now().timestamp_millis())?;
+
+    for raw in due {
+        // Only run the job if it wasn't cancelled in the meantime
+        let removed: usize = client.zrem("jobs", &raw)?;
+        if removed == 0 {
+            continue;
+        }
+
+        let job = match serde_json::from_str::<Job>(&raw) {
+            Ok(job) => job,
+            Err(e) => {
+                error!("Discarding invalid job '{}': {}", raw, e);
+                continue;
+            }
+        };
+
+        if let Err(e) = run(ctx, job) {
+            error!("Job '{}' failed with error: {:?}", raw, e);
+        }
+    }
+
+    Ok(())
+}
+
+// Dispatch a job to its handler
+fn run(ctx: &Context, job: Job) -> CommandResult {
+    match job {
+        Job::Repage { case, attempt } => crate::commands::moderation::repage(ctx, case, attempt),
+        Job::Unmute { user, action } => crate::commands::actions::expire_mute(ctx, user, action),
+        Job::Phase { phase } => crate::commands::phases::transition(ctx, phase),
+        Job::Announce { id } => crate::commands::announcements::post(ctx, id),
+        Job::Remind { item } => crate::commands::agenda::remind(ctx, item),
+        Job::WorkshopReminder { workshop } => crate::commands::workshops::remind(ctx, workshop),
+        Job::WorkshopEnd { workshop } => crate::commands::workshops::end(ctx, workshop),
+    }
+}
@@ -1,8 +1,11 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
 use log::error;
 use std::{env, iter, process::exit};
 
 use rand::{distributions::Alphanumeric, seq::SliceRandom, thread_rng, Rng};
-use serenity::utils::Colour;
+use serenity::model::id::UserId;
+use serenity::utils::{parse_username, Colour};
 
 const COLORS: [Colour; 25] = [
     Colour::BLITZ_BLUE,
@@ -32,37 +35,62 @@ const COLORS: [Colour; 25] = [
     Colour::TEAL,
 ];
 
-// Retrieve ids from environment
+// Retrieve connection details from environment
 lazy_static! {
-    pub static ref DISCORD_TOKEN: String = parse_from_environment::<String>("DISCORD_TOKEN");
-    pub static ref REDIS_URL: String = parse_from_environment::<String>("REDIS_URL");
-    pub static ref TABLES_CATEGORY_ID: u64 = parse_from_environment::<u64>("TABLES_CATEGORY_ID");
-    pub static ref MENTORS_CHANNEL_ID: u64 = parse_from_environment::<u64>("MENTORS_CHANNEL_ID");
-    pub static ref REPORTS_CHANNEL_ID: u64 = parse_from_environment::<u64>("REPORTS_CHANNEL_ID");
-    pub static ref EVERYONE_ROLE_ID: u64 = parse_from_environment::<u64>("EVERYONE_ROLE_ID");
-    pub static ref TEAMLESS_ROLE_ID: u64 = parse_from_environment::<u64>("TEAMLESS_ROLE_ID");
-    pub static ref BOT_ROLE_ID: u64 = parse_from_environment::<u64>("BOT_ROLE_ID");
-    pub static ref MENTOR_ROLE_ID: u64 = parse_from_environment::<u64>("MENTOR_ROLE_ID");
-    pub static ref MANAGER_ROLE_ID: u64 = parse_from_environment::<u64>("MANAGER_ROLE_ID");
+    pub static ref DISCORD_TOKEN: String =
+        parse_from_environment::<String>("DISCORD_TOKEN").unwrap_or_else(|e| fail(&e));
+    pub static ref REDIS_URL: String =
+        parse_from_environment::<String>("REDIS_URL").unwrap_or_else(|e| fail(&e));
 }
 
 // Parse some type from a given environment variable
-fn parse_from_environment<T: std::str::FromStr>(var: &'static str) -> T {
+pub fn parse_from_environment<T: std::str::FromStr>(var: &'static str) -> Result<T, String> {
     let raw = match env::var(var) {
         Ok(raw) => raw,
-        Err(_) => fail(&format!("Variable '{}' is nonexistent! Exiting...", var)),
+        Err(_) => return Err(format!("Variable '{}' is nonexistent!", var)),
     };
 
     match raw.parse::<T>() {
-        Ok(parsed) => parsed,
-        Err(_) => fail(&format!(
-            "Variable '{}' must be of type '{}'! Exiting...",
+        Ok(parsed) => Ok(parsed),
+        Err(_) => Err(format!(
+            "Variable '{}' must be of type '{}'!",
             var,
             std::any::type_name::<T>()
         )),
     }
 }
 
+/// Check all the environment variables at once, exiting if any are invalid
+pub fn check_environment() {
+    let mut errors = Vec::new();
+
+    // Connection details must always be present
+    for var in &["DISCORD_TOKEN", "REDIS_URL"] {
+        if let Err(e) = parse_from_environment::<String>(var) {
+            errors.push(e);
+        }
+    }
+
+    // Ids are optional, but must be valid if present
+    for setting in crate::data::config::SETTINGS {
+        if env::var(setting.variable).is_ok() {
+            if let Err(e) = parse_from_environment::<u64>(setting.variable) {
+                errors.push(e);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        for e in &errors {
+            error!("{}", e);
+        }
+        fail(&format!(
+            "Found {} invalid environment variable(s)! Exiting...",
+            errors.len()
+        ));
+    }
+}
+
 /// Print an error and exit with error code
 pub fn fail(prompt: &'_ str) -> ! {
     error!("{}", prompt);
@@ -82,3 +110,221 @@ pub fn random_string(len: usize) -> String {
         .take(len)
         .collect()
 }
+
+/// Parse a message link into its guild, channel, and message ids
+pub fn parse_message_link(link: &str) -> Option<(u64, u64, u64)> {
+    let path = [
+        "https://discord.com/channels/",
+        "https://discordapp.com/channels/",
+        "https://ptb.discord.com/channels/",
+        "https://canary.discord.com/channels/",
+    ]
+    .iter()
+    .find_map(|prefix| link.strip_prefix(prefix))?;
+
+    let mut ids = path
+        .trim_end_matches('/')
+        .split('/')
+        .map(|id| id.parse::<u64>());
+    match (ids.next(), ids.next(), ids.next(), ids.next()) {
+        (Some(Ok(guild)), Some(Ok(channel)), Some(Ok(message)), None) => {
+            Some((guild, channel, message))
+        }
+        _ => None,
+    }
+}
+
+/// Parse a user from a mention or their id
+pub fn parse_user_id(raw: &str) -> Option<UserId> {
+    parse_username(raw)
+        .or_else(|| raw.parse::<u64>().ok())
+        .map(UserId)
+}
+
+/// Shorten text to at most some number of characters, marking that it was cut off
+pub fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let mut shortened = text
+        .chars()
+        .take(limit.saturating_sub(3))
+        .collect::<String>();
+    shortened.push_str("...");
+    shortened
+}
+
+/// Parse a duration like `30s`, `10m`, `2h`, or `1d` into milliseconds
+pub fn parse_duration(raw: &str) -> Option<i64> {
+    let raw = raw.trim().to_lowercase();
+    let split = raw.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = raw.split_at(split);
+
+    let multiplier = match unit {
+        "s" | "sec" | "secs" => 1_000,
+        "m" | "min" | "mins" => 60_000,
+        "h" | "hr" | "hrs" => 3_600_000,
+        "d" | "day" | "days" => 86_400_000,
+        _ => return None,
+    };
+    match amount.parse::<i64>() {
+        Ok(amount) if amount > 0 => amount.checked_mul(multiplier),
+        _ => None,
+    }
+}
+
+/// Format a duration in milliseconds using its largest whole unit
+pub fn format_duration(ms: i64) -> String {
+    let (amount, unit) = match ms {
+        ms if ms % 86_400_000 == 0 => (ms / 86_400_000, "day"),
+        ms if ms % 3_600_000 == 0 => (ms / 3_600_000, "hour"),
+        ms if ms % 60_000 == 0 => (ms / 60_000, "minute"),
+        ms => (ms / 1_000, "second"),
+    };
+
+    if amount == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", amount, unit)
+    }
+}
+
+/// Parse a time as a duration from now like `in 2h`, a date and time like `2020-10-25 09:00`
+/// in the event timezone, or an RFC 3339 timestamp, returning it in milliseconds
+pub fn parse_time(raw: &str, now: i64, timezone: Tz) -> Option<i64> {
+    let raw = raw.trim();
+    if let Some(duration) = raw.strip_prefix("in ") {
+        return parse_duration(duration).map(|d| now + d);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(raw) {
+        return Some(time.timestamp_millis());
+    }
+
+    let local = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").ok()?;
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+}
+
+/// Format a time in milliseconds in the event timezone
+pub fn format_time(ms: i64, timezone: Tz) -> String {
+    match timezone.timestamp_millis_opt(ms).single() {
+        Some(time) => time.format("%a, %b %-d %Y %-I:%M %p %Z").to_string(),
+        None => "an unknown time".to_string(),
+    }
+}
+
+/// Split a line of CSV into its fields, handling quoted fields
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2020-10-25 09:00 in Los Angeles
+    const MORNING: i64 = 1_603_641_600_000;
+
+    #[test]
+    fn parse_duration_accepts_each_unit() {
+        assert_eq!(parse_duration("30s"), Some(30_000));
+        assert_eq!(parse_duration("10m"), Some(600_000));
+        assert_eq!(parse_duration(" 2HRS "), Some(7_200_000));
+        assert_eq!(parse_duration("1day"), Some(86_400_000));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_durations() {
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("2 hrs"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("5w"), None);
+        assert_eq!(parse_duration("-5m"), None);
+        assert_eq!(parse_duration("99999999999999999d"), None);
+    }
+
+    #[test]
+    fn format_duration_uses_the_largest_whole_unit() {
+        assert_eq!(format_duration(86_400_000), "1 day");
+        assert_eq!(format_duration(7_200_000), "2 hours");
+        assert_eq!(format_duration(5_400_000), "90 minutes");
+        assert_eq!(format_duration(1_000), "1 second");
+    }
+
+    #[test]
+    fn parse_time_accepts_relative_local_and_rfc3339_times() {
+        let timezone: Tz = "America/Los_Angeles".parse().unwrap();
+        assert_eq!(parse_time("in 2h", 1_000, timezone), Some(7_201_000));
+        assert_eq!(parse_time("2020-10-25 09:00", 0, timezone), Some(MORNING));
+        assert_eq!(
+            parse_time(" 2020-10-25T16:00:00Z ", 0, timezone),
+            Some(MORNING)
+        );
+        assert_eq!(
+            parse_time("2020-10-25T09:00:00-07:00", 0, timezone),
+            Some(MORNING)
+        );
+        assert_eq!(parse_time("2020-10-25 16:00", 0, Tz::UTC), Some(MORNING));
+    }
+
+    #[test]
+    fn parse_time_handles_daylight_saving_changes() {
+        let timezone: Tz = "America/Los_Angeles".parse().unwrap();
+        // Clocks skip from 2:00 to 3:00
+        assert_eq!(parse_time("2020-03-08 02:30", 0, timezone), None);
+        // Clocks repeat 1:00 to 2:00, so the earlier one is used
+        assert_eq!(
+            parse_time("2020-11-01 01:30", 0, timezone),
+            Some(1_604_219_400_000)
+        );
+    }
+
+    #[test]
+    fn parse_time_rejects_invalid_times() {
+        let timezone = Tz::UTC;
+        assert_eq!(parse_time("in 2", 0, timezone), None);
+        assert_eq!(parse_time("tomorrow", 0, timezone), None);
+        assert_eq!(parse_time("2020-13-01 09:00", 0, timezone), None);
+        assert_eq!(parse_time("2020-10-25", 0, timezone), None);
+    }
+
+    #[test]
+    fn truncate_marks_shortened_text() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate("a bit too long", 10), "a bit t...");
+        // Characters are counted rather than bytes
+        assert_eq!(truncate("ééééé", 4), "é...");
+    }
+
+    #[test]
+    fn parse_user_id_accepts_mentions_and_ids() {
+        assert_eq!(parse_user_id("<@1234>"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("<@!1234>"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("1234"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("<#1234>"), None);
+        assert_eq!(parse_user_id("someone"), None);
+    }
+}